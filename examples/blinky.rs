@@ -27,13 +27,14 @@ use cortex_m_rt::entry;
 //use cortex_m_semihosting::hprintln; //uncomment this line if you want text output to semihosting.
 use cortex_m::interrupt::free;
 
-use psoc6_hal::{psoc::Psoc, delay::Delay};
+use psoc6_hal::{clocks::ClockConfig, psoc::Psoc, delay::Delay};
 use psoc6_hal::prelude::*;
 
 #[entry]
 fn main() -> ! {
     let psoc = Psoc::new();
     let cp = cortex_m::Peripherals::take().unwrap();
+    let clocks = psoc.start_system_clocks(ClockConfig::new()).unwrap();
 
     let ( mut led3, mut led4) = free(|cs| {(
             psoc.gpio.p6_3.into_strong_output(cs),
@@ -41,7 +42,7 @@ fn main() -> ! {
         )});
 
 
-    let mut delay = Delay::new(cp.SYST);
+    let mut delay = Delay::new(cp.SYST, &clocks);
 
     loop {
         
@@ -25,9 +25,12 @@ extern crate panic_semihosting;
 use cortex_m_rt::entry;
 use cortex_m::interrupt::free;
 
+use psoc6_hal::clocks::ClockConfig;
 use psoc6_hal::delay::Delay;
 use psoc6_hal::prelude::*;
 use psoc6_hal::pac::Peripherals;
+use psoc6_hal::drivers::cpuss::Cpuss;
+use psoc6_hal::drivers::flashc::Flash;
 use psoc6_hal::drivers::system::System;
 use psoc6_hal::drivers::system::reset_cause;
 #[cfg(not(armv7m))]
@@ -37,7 +40,10 @@ fn main() -> ! {
     let cp = cortex_m::Peripherals::take().unwrap();
 
     let system = System::from(p.SRSS);
-    system.wdt_start(6000u32);
+    let cpuss = Cpuss::from(p.CPUSS);
+    let flash = Flash::from(p.FLASHC);
+    let clocks = ClockConfig::new().freeze(&system, &cpuss, &flash).unwrap();
+    system.wdt_start_with_clocks(6000u32, &clocks);
     let gpio = p.GPIO.split();
 
     let (mut led_red, mut led_green) = free(|cs| {(
@@ -45,7 +51,7 @@ fn main() -> ! {
             gpio.p7_1.into_strong_output(cs),
         )});
 
-    let mut delay = Delay::new(cp.SYST);
+    let mut delay = Delay::new(cp.SYST, &clocks);
 
     //When a reset is caused by the WDT only the led_green will be on
     // Pressing the reset button on the board will cause both the
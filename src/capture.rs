@@ -0,0 +1,259 @@
+//! Input capture
+//!
+//! Capture runs a TCPWM counter in the capture mode, free running over
+//! its whole range and timestamping both edges of an input pin. On each
+//! edge the hardware moves CC to CC_BUFF and copies the count to CC, so
+//! on a rising edge CC holds the rising edge and CC_BUFF the preceding
+//! falling edge. From two rising edges the period, pulse width and duty
+//! cycle are calculated, with the counter overflows between them
+//! counted in software.
+//! Measurements are made either once, by polling measure, or
+//! continuously from the counter interrupt with on_interrupt.
+//! The low time of the input must be shorter than the counter range and
+//! the high time long enough for the pin level to still be high when
+//! the capture is handled, it is used to tell the edges apart. A rising
+//! edge handled with the pin low is dropped along with the previous
+//! rising edge, so no measurement is made rather than one spanning two
+//! cycles.
+//!```
+//! let mut capture = Capture::new(counters.counter0, &dividers.div16_0, pin, Prescaler::Div1);
+//! capture.enable();
+//! let measurement = nb::block!(capture.measure())?;
+//! let hz = measurement.frequency_hz();
+//!```
+
+use crate::drivers::cpuss::interrupt::InterruptSource;
+use crate::drivers::peri::PeriClockDivider;
+use crate::drivers::tcpwm::{
+    Control, CounterEvent, CounterMode, InputEdge, Prescaler, TcpwmCounter, TriggerInput,
+};
+use crate::error::Error;
+use crate::gpio::AlternateInput;
+use crate::trigger::{TriggerPin, TriggerSource};
+
+/// Measurement is the period and pulse width of one input cycle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measurement {
+    /// period from rising edge to rising edge in counter ticks.
+    pub period: u64,
+    /// pulse_width from rising edge to falling edge in counter ticks.
+    pub pulse_width: u64,
+    /// tick_hz is the counter clock rate.
+    pub tick_hz: u32,
+}
+
+impl Measurement {
+    /// frequency_hz returns the input frequency, 0 when the period is
+    /// longer than a second.
+    pub fn frequency_hz(&self) -> u32 {
+        match self.period {
+            0 => 0,
+            period => (self.tick_hz as u64 / period) as u32,
+        }
+    }
+    /// period_us returns the period in microseconds.
+    pub fn period_us(&self) -> u64 {
+        ticks_to_us(self.period, self.tick_hz)
+    }
+    /// pulse_width_us returns the pulse width in microseconds.
+    pub fn pulse_width_us(&self) -> u64 {
+        ticks_to_us(self.pulse_width, self.tick_hz)
+    }
+    /// duty_cycle returns the pulse width as a fraction of the period
+    /// in parts of scale, e.g. 1000 for per mille.
+    pub fn duty_cycle(&self, scale: u32) -> u32 {
+        match self.period {
+            0 => 0,
+            period => (self.pulse_width * scale as u64 / period) as u32,
+        }
+    }
+}
+
+#[inline(always)]
+fn ticks_to_us(ticks: u64, tick_hz: u32) -> u64 {
+    match tick_hz {
+        0 => 0,
+        hz => ticks * 1_000_000 / hz as u64,
+    }
+}
+
+/// Capture is a TCPWM counter in the capture mode owning its input pin.
+pub struct Capture<C: TcpwmCounter, P: AlternateInput> {
+    counter: C,
+    pin: TriggerPin<P>,
+    tick_hz: u32,
+    overflows: u64,
+    last_rise: Option<u64>,
+    last: Option<Measurement>,
+}
+
+impl<C: TcpwmCounter, P: AlternateInput> Capture<C, P> {
+    /// new connects the divider to the counter and configures a stopped
+    /// capture of both edges of the pin. The divider must be set and
+    /// enabled.
+    pub fn new<D: PeriClockDivider>(
+        mut counter: C,
+        divider: &D,
+        pin: TriggerPin<P>,
+        prescaler: Prescaler,
+    ) -> Self {
+        divider.connect(C::CLOCK);
+        counter.disable();
+        counter.configure(Control {
+            mode: CounterMode::Capture,
+            generic: prescaler as u8,
+            ..Control::default()
+        });
+        counter.set_inputs(
+            pin.input(),
+            TriggerInput::One,
+            TriggerInput::Zero,
+            TriggerInput::Zero,
+            TriggerInput::Zero,
+        );
+        counter.set_input_edges(
+            InputEdge::Both,
+            InputEdge::Level,
+            InputEdge::Rising,
+            InputEdge::Rising,
+            InputEdge::Rising,
+        );
+        counter.set_period(C::MAX);
+        Capture {
+            counter,
+            pin,
+            tick_hz: divider.frequency() >> (prescaler as u32),
+            overflows: 0,
+            last_rise: None,
+            last: None,
+        }
+    }
+    /// tick_hz returns the counter clock rate after the prescaler.
+    #[inline(always)]
+    pub fn tick_hz(&self) -> u32 {
+        self.tick_hz
+    }
+    /// enable starts the counter from 0.
+    pub fn enable(&mut self) -> () {
+        self.restart();
+        self.counter.enable();
+        self.counter.start();
+    }
+    /// disable stops the counter.
+    #[inline(always)]
+    pub fn disable(&mut self) -> () {
+        self.counter.disable();
+    }
+    /// listen forwards the capture and overflow events to the counter
+    /// interrupt for continuous measurement, see on_interrupt.
+    pub fn listen(&mut self) -> () {
+        self.counter.listen(CounterEvent::CompareMatch);
+        self.counter.listen(CounterEvent::TerminalCount);
+    }
+    /// unlisten stops forwarding the events to the counter interrupt.
+    pub fn unlisten(&mut self) -> () {
+        self.counter.unlisten(CounterEvent::CompareMatch);
+        self.counter.unlisten(CounterEvent::TerminalCount);
+    }
+    /// interrupt returns the interrupt source of the counter.
+    #[inline(always)]
+    pub fn interrupt(&self) -> InterruptSource {
+        C::INTERRUPT
+    }
+    /// on_interrupt handles the pending capture and overflow events, it
+    /// returns a measurement completed by a rising edge.
+    pub fn on_interrupt(&mut self) -> Option<Measurement> {
+        let overflow = self.counter.is_pending(CounterEvent::TerminalCount);
+        let mut measurement = None;
+        if self.counter.is_pending(CounterEvent::CompareMatch) {
+            self.counter.clear_interrupt(CounterEvent::CompareMatch);
+            let cc = self.counter.cc();
+            // A capture in the first half of the range with an overflow
+            // pending was made after the overflow.
+            let overflows = match overflow && cc <= C::MAX / 2 {
+                true => self.overflows + 1,
+                false => self.overflows,
+            };
+            match self.pin.is_high() {
+                true => measurement = self.rising_edge(overflows, cc, self.counter.cc_buff()),
+                false => self.falling_edge(self.counter.cc_buff()),
+            }
+        }
+        if overflow {
+            self.counter.clear_interrupt(CounterEvent::TerminalCount);
+            self.overflows += 1;
+        }
+        measurement
+    }
+    /// last_measurement returns the latest measurement.
+    #[inline(always)]
+    pub fn last_measurement(&self) -> Option<Measurement> {
+        self.last
+    }
+    /// measure polls for a measurement started by the first call, it
+    /// needs two rising edges and a falling edge between them. The
+    /// counter must have been enabled.
+    /// It returns an error when the counter is not running.
+    pub fn measure(&mut self) -> nb::Result<Measurement, Error> {
+        if !self.counter.is_running() {
+            return Err(nb::Error::Other(Error::TimerNotRunning));
+        }
+        if self.last.is_some() {
+            self.last_rise = None;
+            self.last = None;
+        }
+        match self.on_interrupt() {
+            Some(measurement) => Ok(measurement),
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+    /// free stops the counter and returns the counter and the pin.
+    pub fn free(mut self) -> (C, TriggerPin<P>) {
+        self.counter.disable();
+        (self.counter, self.pin)
+    }
+
+    /// restart clears the counter, the pending events and the edges.
+    fn restart(&mut self) -> () {
+        self.counter.set_counter(0);
+        self.counter.clear_interrupt(CounterEvent::CompareMatch);
+        self.counter.clear_interrupt(CounterEvent::TerminalCount);
+        self.overflows = 0;
+        self.last_rise = None;
+        self.last = None;
+    }
+    /// falling_edge checks a capture made with the pin low, CC_BUFF
+    /// holds the preceding rising edge. When it is not the last rising
+    /// edge seen, a rising edge was handled after the pin went low, the
+    /// next rising edge starts a new period.
+    fn falling_edge(&mut self, cc_buff: u32) -> () {
+        let range = C::MAX as u64 + 1;
+        if self.last_rise.map(|last_rise| last_rise % range) != Some(cc_buff as u64) {
+            self.last_rise = None;
+        }
+    }
+    /// rising_edge records a rising edge at cc, CC_BUFF holds the
+    /// preceding falling edge.
+    fn rising_edge(&mut self, overflows: u64, cc: u32, cc_buff: u32) -> Option<Measurement> {
+        let range = C::MAX as u64 + 1;
+        let rise = overflows * range + cc as u64;
+        let low = (cc as u64 + range - cc_buff as u64) % range;
+        let measurement = self.last_rise.and_then(|last_rise| {
+            let period = rise - last_rise;
+            match low < period {
+                true => Some(Measurement {
+                    period,
+                    pulse_width: period - low,
+                    tick_hz: self.tick_hz,
+                }),
+                // No falling edge between the rising edges.
+                false => None,
+            }
+        });
+        self.last_rise = Some(rise);
+        if measurement.is_some() {
+            self.last = measurement;
+        }
+        measurement
+    }
+}
@@ -1,31 +1,609 @@
 //! clocks.rs implements high level clock methods
+//! A ClockConfig describes the required clock tree, freezing it
+//! applies the configuration to the hardware and returns a Clocks
+//! record of the resulting frequencies. The Clocks record is then
+//! passed to the timing dependent drivers, e.g. Delay and the watchdog.
+//!```
+//! let clocks = ClockConfig::new()
+//!     .fll(100_000_000)
+//!     .hf_root(RootClocks::CpuPeriAhb, ClockPath::Path0, Divider::NoDiv)
+//!     .peri_div(1)
+//!     .freeze(&system, &cpuss, &flash)?;
+//! let mut delay = Delay::new(cp.SYST, &clocks);
+//!```
 
-use crate::psoc::Psoc;
+pub mod builder;
+pub mod control;
 
-pub struct DerivedClock {}
-pub struct RealTimeClock {}
+use crate::drivers::backup::calendar::{DateTime, DstRule};
+use crate::drivers::backup::rtc::{Alarm, AlarmId, HourFormat};
+use crate::drivers::backup::Backup;
+use crate::drivers::cpuss::Cpuss;
+use crate::drivers::flashc::Flash;
+use crate::drivers::peri::{PeriClockDivider, PeripheralClock};
+pub use crate::drivers::system::clocks::Clocks as ClockSource;
+use crate::drivers::system::{
+    ClockPath, Divider, EcoConfig, FllConfig, PathSource, PllConfig, RootClocks, System,
+};
+use crate::error::Error;
+use crate::gpio::{Alternate, HighZ, Hsiom, Strong, P0_5};
+use crate::psoc::{Psoc, SystemMode};
+use cortex_m::interrupt::CriticalSection;
 
-impl DerivedClock {
-    pub fn new(freq: u32) -> DerivedClock {
-        let _ = freq;
-        DerivedClock {}
+pub const IMO_FREQ: u32 = 8_000_000;
+pub const ILO_FREQ: u32 = 32_000;
+pub const WCO_FREQ: u32 = 32_768;
+pub const PILO_FREQ: u32 = 32_768;
+
+// Reference clock accuracy used when solving the FLL, +/-1% IMO.
+pub(crate) const FLL_REFERENCE_TOLERANCE_PPM: u32 = 10_000;
+const FLL_STARTUP_TIMEOUT_US: u32 = 200_000;
+const PLL_STARTUP_TIMEOUT_US: u32 = 200_000;
+const ECO_STARTUP_TIMEOUT_US: u32 = 1_000_000;
+
+/// DerivedClock is a peripheral clock divider set to a frequency
+/// derived from CLK_PERI and enabled.
+pub struct DerivedClock<D: PeriClockDivider> {
+    divider: D,
+    freq: u32,
+}
+
+/// RealTimeClock is the backup domain RTC with an optional DST rule.
+/// With a rule the RTC counts standard time and now returns the local
+/// time, so the clock needs no adjusting at the DST changes and keeps
+/// the right time through a reset or Hibernate. The alarms match the
+/// RTC, i.e. standard time.
+///```
+/// let mut rtc = RealTimeClock::new(backup, HourFormat::H24);
+/// rtc.set_dst_rule(Some(DstRule::european_union(1)));
+/// rtc.set(&DateTime::new(2021, 7, 1, 11, 0, 0)?)?;
+/// let local = rtc.now()?; // 12:00 in summer
+///```
+pub struct RealTimeClock {
+    backup: Backup,
+    format: HourFormat,
+    dst: Option<DstRule>,
+}
+
+/// Maximum frequency of a fast strong GPIO output, datasheet GPIO AC
+/// specifications.
+pub const CLK_EXT_MAX_FREQ: u32 = 80_000_000;
+/// Maximum frequency of an external clock input, datasheet EXTCLK
+/// specifications.
+pub const EXT_CLK_MAX_FREQ: u32 = 100_000_000;
+// HSIOM connection of srss.ext_clk on P0.5, HSIOM_SEL 14.
+const CLK_EXT_HSIOM: Hsiom = Hsiom::Act6;
+
+/// ExtClk is an external clock input on the clk_ext pin P0.5. Holding
+/// an ExtClk is proof that the pin is connected to EXTCLK, it is passed
+/// to ClockConfig::ext_clk to use PathSource::ExtClk.
+pub struct ExtClk {
+    pin: P0_5<Alternate<HighZ>>,
+    freq: u32,
+}
+
+impl ExtClk {
+    /// new connects the clk_ext pin to EXTCLK and records the frequency
+    /// of the clock supplied to it.
+    /// It returns an error when freq_hz is 0 or above EXT_CLK_MAX_FREQ.
+    ///```
+    /// let ext_clk = ExtClk::new(p0_5, 24_000_000, cs)?;
+    /// let clocks = ClockConfig::new()
+    ///     .ext_clk(&ext_clk)
+    ///     .path_source(ClockPath::Path0, PathSource::ExtClk)
+    ///     .freeze(&system, &cpuss, &flash)?;
+    ///```
+    pub fn new<MODE>(pin: P0_5<MODE>, freq_hz: u32, cs: &CriticalSection) -> Result<Self, Error> {
+        if freq_hz == 0 || freq_hz > EXT_CLK_MAX_FREQ {
+            return Err(Error::ExtClkFrequencyOutOfRange);
+        }
+        let pin = pin.into_alternate_input(CLK_EXT_HSIOM, cs);
+        Ok(ExtClk { pin, freq: freq_hz })
+    }
+    /// freq returns the recorded external clock frequency.
+    #[inline(always)]
+    pub fn freq(&self) -> u32 {
+        self.freq
+    }
+    /// free releases the pin, no path may still use PathSource::ExtClk.
+    pub fn free(self) -> P0_5<Alternate<HighZ>> {
+        self.pin
     }
 }
-impl Psoc {
-    pub fn start_system_clocks(&self) {
-        //config & start root clocks and paths
-        //config &start fll
-        //adjust wait states for clock speed
+
+/// ClockOutput drives CLK_HF4, a path clock with a divider, onto the
+/// clk_ext pin P0.5 to clock external devices or check a clock with a
+/// scope.
+pub struct ClockOutput {
+    pin: P0_5<Alternate<Strong>>,
+    freq: u32,
+}
+
+impl ClockOutput {
+    /// new routes a path through CLK_HF4 to the clk_ext pin.
+    /// It returns an error when the path is not running or the output
+    /// would exceed the pin limit.
+    ///```
+    /// // 50MHz FLL divided by 4 on P0.5
+    /// let clk_out = ClockOutput::new(p0_5, &system, &clocks, ClockPath::Path0, Divider::Div4, cs)?;
+    ///```
+    pub fn new<MODE>(
+        pin: P0_5<MODE>,
+        system: &System,
+        clocks: &Clocks,
+        path: ClockPath,
+        div: Divider,
+        cs: &CriticalSection,
+    ) -> Result<Self, Error> {
+        let freq = clocks.path(path) >> (div as u32);
+        if freq == 0 || freq > CLK_EXT_MAX_FREQ {
+            return Err(Error::ClockOutputFrequencyOutOfRange);
+        }
+        system.stop_root_clk(RootClocks::ClkOut);
+        system.configure_root_clk_source(RootClocks::ClkOut, path);
+        system.configure_root_clk_div(RootClocks::ClkOut, div);
+        system.start_root_clk(RootClocks::ClkOut);
+        let pin = pin.into_alternate_strong_output(CLK_EXT_HSIOM, cs);
+        Ok(ClockOutput { pin, freq })
+    }
+    /// freq returns the frequency on the clk_ext pin.
+    #[inline(always)]
+    pub fn freq(&self) -> u32 {
+        self.freq
+    }
+    /// free stops CLK_HF4 and releases the pin.
+    pub fn free(self, system: &System) -> P0_5<Alternate<Strong>> {
+        system.stop_root_clk(RootClocks::ClkOut);
+        self.pin
+    }
+}
+
+impl<D: PeriClockDivider> DerivedClock<D> {
+    /// new sets the divider as close to freq as possible and enables it.
+    pub fn new(mut divider: D, clocks: &Clocks, freq: u32) -> Result<Self, Error> {
+        let freq = divider.set_frequency(clocks, freq)?;
+        divider.enable();
+        Ok(DerivedClock { divider, freq })
+    }
+    /// freq returns the achieved frequency.
+    #[inline(always)]
+    pub fn freq(&self) -> u32 {
+        self.freq
+    }
+    /// connect clocks a peripheral from this clock.
+    #[inline(always)]
+    pub fn connect(&self, pclk: PeripheralClock) -> () {
+        self.divider.connect(pclk);
+    }
+    /// free disables the clock and releases the divider.
+    pub fn free(mut self) -> D {
+        self.divider.disable();
+        self.divider
+    }
+}
+
+impl RealTimeClock {
+    /// new takes the backup domain, the RTC keeps its date and time.
+    /// The backup clock should be the WCO, see System::start_wco_lfclk.
+    pub fn new(backup: Backup, format: HourFormat) -> Self {
+        RealTimeClock {
+            backup,
+            format,
+            dst: None,
+        }
+    }
+    /// set_dst_rule sets or removes the DST rule, set the standard time
+    /// again when changing it.
+    #[inline(always)]
+    pub fn set_dst_rule(&mut self, dst: Option<DstRule>) -> () {
+        self.dst = dst;
+    }
+    /// set writes the standard time.
+    #[inline(always)]
+    pub fn set(&self, standard: &DateTime) -> Result<(), Error> {
+        self.backup.rtc_set_date_time(standard, self.format)
+    }
+    /// set_local writes a local time, it is taken as standard time in
+    /// the ambiguous hour at the end of DST.
+    pub fn set_local(&self, local: &DateTime) -> Result<(), Error> {
+        let standard = match self.dst {
+            Some(dst) => {
+                let shifted = local.add_seconds(-(dst.offset_minutes as i32 * 60));
+                match dst.is_dst(&shifted) && dst.is_dst(local) {
+                    true => shifted,
+                    false => *local,
+                }
+            }
+            None => *local,
+        };
+        self.set(&standard)
+    }
+    /// standard returns the standard time of the RTC.
+    #[inline(always)]
+    pub fn standard(&self) -> Result<DateTime, Error> {
+        self.backup.rtc_date_time()
+    }
+    /// now returns the local time.
+    pub fn now(&self) -> Result<DateTime, Error> {
+        let standard = self.standard()?;
+        Ok(match self.dst {
+            Some(dst) => dst.to_local(&standard),
+            None => standard,
+        })
+    }
+    /// is_dst returns true when DST applies now.
+    pub fn is_dst(&self) -> Result<bool, Error> {
+        let standard = self.standard()?;
+        Ok(self.dst.map_or(false, |dst| dst.is_dst(&standard)))
+    }
+    /// next_dst_change returns the standard time of the next DST start
+    /// or end and true for a start, e.g. to set an alarm for it.
+    pub fn next_dst_change(&self) -> Result<Option<(DateTime, bool)>, Error> {
+        let standard = self.standard()?;
+        Ok(self.dst.map(|dst| dst.next_transition(&standard)))
+    }
+    /// set_alarm sets an alarm in standard time.
+    #[inline(always)]
+    pub fn set_alarm(&self, id: AlarmId, alarm: &Alarm) -> Result<(), Error> {
+        self.backup.rtc_set_alarm(id, alarm)
+    }
+    /// backup returns the backup domain, e.g. to listen to the alarms.
+    #[inline(always)]
+    pub fn backup(&self) -> &Backup {
+        &self.backup
+    }
+    /// free returns the backup domain, the RTC keeps running.
+    pub fn free(self) -> Backup {
+        self.backup
+    }
+}
+
+/// ClockConfig is a builder for the clock tree. Nothing is written to
+/// the hardware until freeze is called.
+/// The default configuration matches System::configure_system_clocks:
+/// the ILO for LFCLK, the IMO on every path, the FLL at 100MHz on
+/// CLK_PATH0 driving CLK_HF0 and no CPU dividers.
+#[derive(Clone, Copy, Debug)]
+pub struct ClockConfig {
+    system_mode: SystemMode,
+    lfclk: ClockSource,
+    lfclk_hz: Option<u32>,
+    eco: Option<EcoConfig>,
+    ext_clk_hz: Option<u32>,
+    path_sources: [PathSource; 5],
+    fll_hz: Option<u32>,
+    pll_hz: Option<u32>,
+    hf_roots: [Option<(ClockPath, Divider)>; 5],
+    fast_div: u8,
+    peri_div: u8,
+    slow_div: u8,
+}
+
+impl ClockConfig {
+    pub fn new() -> Self {
+        Self {
+            system_mode: SystemMode::Lp,
+            lfclk: ClockSource::Ilo,
+            lfclk_hz: None,
+            eco: None,
+            ext_clk_hz: None,
+            path_sources: [PathSource::Imo; 5],
+            fll_hz: Some(100_000_000),
+            pll_hz: None,
+            hf_roots: [
+                Some((ClockPath::Path0, Divider::NoDiv)),
+                None,
+                None,
+                None,
+                None,
+            ],
+            fast_div: 0,
+            peri_div: 0,
+            slow_div: 0,
+        }
+    }
+    /// system_mode selects the Lp or Ulp frequency limits and wait states.
+    pub fn system_mode(mut self, mode: SystemMode) -> Self {
+        self.system_mode = mode;
+        self
+    }
+    /// lfclk selects the LFCLK source, one of Ilo, Wco or Pilo. The WCO
+    /// and PILO must already be running, see System::start_wco_lfclk.
+    pub fn lfclk(mut self, source: ClockSource) -> Self {
+        self.lfclk = source;
+        self
+    }
+    /// lfclk_freq records a measured LFCLK frequency in place of the
+    /// nominal frequency of the source, see System::trim_ilo.
+    pub fn lfclk_freq(mut self, lfclk_hz: u32) -> Self {
+        self.lfclk_hz = Some(lfclk_hz);
+        self
+    }
+    /// eco configures and starts the ECO so it can be used as a path source.
+    pub fn eco(mut self, eco_config: EcoConfig) -> Self {
+        self.eco = Some(eco_config);
+        self
+    }
+    /// ext_clk records the frequency of the external clock so it can be
+    /// used as a path source.
+    pub fn ext_clk(mut self, ext_clk: &ExtClk) -> Self {
+        self.ext_clk_hz = Some(ext_clk.freq());
+        self
+    }
+    pub fn path_source(mut self, path: ClockPath, source: PathSource) -> Self {
+        self.path_sources[path as usize] = source;
+        self
+    }
+    /// fll sets the FLL output frequency on CLK_PATH0, 24-100MHz.
+    pub fn fll(mut self, target_hz: u32) -> Self {
+        self.fll_hz = Some(target_hz);
+        self
+    }
+    /// no_fll bypasses the FLL so CLK_PATH0 runs from its source.
+    pub fn no_fll(mut self) -> Self {
+        self.fll_hz = None;
+        self
+    }
+    /// pll sets the PLL output frequency on CLK_PATH1, 10.625-150MHz.
+    pub fn pll(mut self, target_hz: u32) -> Self {
+        self.pll_hz = Some(target_hz);
+        self
+    }
+    /// hf_root enables a CLK_HF root clock from a path with a divider.
+    pub fn hf_root(mut self, root: RootClocks, path: ClockPath, div: Divider) -> Self {
+        self.hf_roots[root as usize] = Some((path, div));
+        self
+    }
+    /// cm4_fast_div sets CLK_FAST = CLK_HF0 / (fast_div + 1).
+    pub fn cm4_fast_div(mut self, fast_div: u8) -> Self {
+        self.fast_div = fast_div;
+        self
+    }
+    /// peri_div sets CLK_PERI = CLK_HF0 / (peri_div + 1).
+    pub fn peri_div(mut self, peri_div: u8) -> Self {
+        self.peri_div = peri_div;
+        self
+    }
+    /// cm0_slow_div sets CLK_SLOW = CLK_PERI / (slow_div + 1).
+    pub fn cm0_slow_div(mut self, slow_div: u8) -> Self {
+        self.slow_div = slow_div;
+        self
+    }
+
+    /// path_source_freq returns the frequency of the source selected
+    /// for a path, before the FLL or PLL.
+    fn path_source_freq(&self, path: usize) -> Result<u32, Error> {
+        match self.path_sources[path] {
+            PathSource::Imo => Ok(IMO_FREQ),
+            PathSource::Eco => match &self.eco {
+                Some(eco) => Ok(eco.freq()),
+                None => Err(Error::UnknownPathSource),
+            },
+            PathSource::ExtClk => self.ext_clk_hz.ok_or(Error::UnknownPathSource),
+            _ => Err(Error::UnknownPathSource),
+        }
+    }
+
+    /// calculate returns the frequencies this configuration produces
+    /// without touching the hardware.
+    pub fn calculate(&self) -> Result<Clocks, Error> {
+        let nominal_lf = match self.lfclk {
+            ClockSource::Ilo => ILO_FREQ,
+            ClockSource::Wco => WCO_FREQ,
+            ClockSource::Pilo => PILO_FREQ,
+            _ => return Err(Error::UnknownLfClkSource),
+        };
+        let lf = self.lfclk_hz.unwrap_or(nominal_lf);
+        let mut paths = [0u32; 5];
+        for path in 0..paths.len() {
+            paths[path] = self.path_source_freq(path)?;
+        }
+        if let Some(target) = self.fll_hz {
+            paths[0] = FllConfig::for_target(paths[0], target, FLL_REFERENCE_TOLERANCE_PPM)?
+                .output_freq(paths[0]);
+        }
+        if let Some(target) = self.pll_hz {
+            paths[1] = PllConfig::for_target(paths[1], target)?.output_freq(paths[1]);
+        }
+        let mut hf = [0u32; 5];
+        for (root, config) in self.hf_roots.iter().enumerate() {
+            if let Some((path, div)) = config {
+                hf[root] = paths[*path as usize] >> (*div as u32);
+            }
+        }
+        if hf[0] == 0 {
+            return Err(Error::ClockHf0NotConfigured);
+        }
+        let fast = hf[0] / (self.fast_div as u32 + 1);
+        let peri = hf[0] / (self.peri_div as u32 + 1);
+        let slow = peri / (self.slow_div as u32 + 1);
+
+        let (max_hf, max_peri) = match self.system_mode {
+            SystemMode::Ulp => (ULP_MAX_HF_FREQ, ULP_MAX_PERI_FREQ),
+            _ => (LP_MAX_HF_FREQ, LP_MAX_PERI_FREQ),
+        };
+        if hf.iter().any(|f| *f > max_hf) || fast > max_hf || peri > max_peri || slow > max_peri {
+            return Err(Error::ClockFrequencyExceedsSystemModeLimit);
+        }
+        Ok(Clocks {
+            lf,
+            ext: self.ext_clk_hz.unwrap_or(0),
+            paths,
+            hf,
+            fast,
+            peri,
+            slow,
+        })
+    }
+
+    /// freeze applies the configuration and returns the resulting
+    /// clock frequencies. The sequence follows trm 20.3:
+    /// 1. Wait states are set for the maximum frequency of the mode and
+    ///    the CPU dividers to the larger of their current and new values,
+    ///    so CLK_PERI and CLK_FAST stay within limits while CLK_HF0 changes.
+    /// 2. LFCLK, ECO and path sources are selected.
+    /// 3. The FLL and PLL are configured and locked.
+    /// 4. CLK_HF roots are selected, divided and enabled.
+    /// 5. The CPU and peripheral dividers are set.
+    /// 6. Wait states are reduced to suit the CLK_HF0 frequency.
+    pub fn freeze(self, system: &System, cpuss: &Cpuss, flash: &Flash) -> Result<Clocks, Error> {
+        let clocks = self.calculate()?;
+        let max_hf = match self.system_mode {
+            SystemMode::Ulp => ULP_MAX_HF_FREQ,
+            _ => LP_MAX_HF_FREQ,
+        };
+        cpuss.configure_wait_states(max_hf, &self.system_mode);
+        flash.configure_wait_states(max_hf, &self.system_mode);
+        let current = system.clock_tree(cpuss);
+        cpuss.configure_clocks_cm4(self.fast_div.max(current.fast_int_div));
+        cpuss.configure_clocks_cm0(
+            self.peri_div.max(current.peri_int_div),
+            self.slow_div.max(current.slow_int_div),
+        );
+
+        system.configure_lfclk_source(self.lfclk)?;
+        if let Some(eco_config) = &self.eco {
+            system.configure_eco(eco_config);
+            system.start_eco(ECO_STARTUP_TIMEOUT_US)?;
+        }
+        for (path, source) in self.path_sources.iter().enumerate() {
+            system.configure_path_source(PATHS[path], *source);
+        }
+        match self.fll_hz {
+            Some(target) => {
+                let reference = self.path_source_freq(ClockPath::Path0 as usize)?;
+                let fll_config =
+                    FllConfig::for_target(reference, target, FLL_REFERENCE_TOLERANCE_PPM)?;
+                system.configure_fll(fll_config);
+                system.start_fll(FLL_STARTUP_TIMEOUT_US)?;
+            }
+            None => system.stop_fll(),
+        }
+        match self.pll_hz {
+            Some(target) => {
+                let reference = self.path_source_freq(ClockPath::Path1 as usize)?;
+                system.configure_pll(PllConfig::for_target(reference, target)?);
+                system.start_pll(PLL_STARTUP_TIMEOUT_US)?;
+            }
+            None => system.stop_pll(),
+        }
+        for (root, config) in self.hf_roots.iter().enumerate() {
+            if let Some((path, div)) = config {
+                system.configure_root_clk_source(ROOTS[root], *path);
+                system.configure_root_clk_div(ROOTS[root], *div);
+                system.start_root_clk(ROOTS[root]);
+            }
+        }
+        cpuss.configure_clocks_cm4(self.fast_div);
+        cpuss.configure_clocks_cm0(self.peri_div, self.slow_div);
+
+        cpuss.configure_wait_states(clocks.hf(RootClocks::CpuPeriAhb), &self.system_mode);
+        flash.configure_wait_states(clocks.hf(RootClocks::CpuPeriAhb), &self.system_mode);
+        Ok(clocks)
+    }
+}
+
+const PATHS: [ClockPath; 5] = [
+    ClockPath::Path0,
+    ClockPath::Path1,
+    ClockPath::Path2,
+    ClockPath::Path3,
+    ClockPath::Path4,
+];
+const ROOTS: [RootClocks; 5] = [
+    RootClocks::CpuPeriAhb,
+    RootClocks::PdmPcmI2s,
+    RootClocks::Smif,
+    RootClocks::Usb,
+    RootClocks::ClkOut,
+];
+
+/// Maximum frequencies for each SystemMode, datasheet table
+/// "Clock frequency limits".
+pub const LP_MAX_HF_FREQ: u32 = 150_000_000;
+pub const LP_MAX_PERI_FREQ: u32 = 100_000_000;
+pub const ULP_MAX_HF_FREQ: u32 = 50_000_000;
+pub const ULP_MAX_PERI_FREQ: u32 = 25_000_000;
+
+/// Clocks is a frozen record of the clock frequencies in Hz. It can
+/// only be created by applying a ClockConfig, so holding a Clocks is
+/// proof that the hardware runs at these frequencies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clocks {
+    lf: u32,
+    ext: u32,
+    paths: [u32; 5],
+    hf: [u32; 5],
+    fast: u32,
+    peri: u32,
+    slow: u32,
+}
+
+impl Clocks {
+    /// lf_clk returns the LFCLK frequency clocking the watchdog.
+    #[inline(always)]
+    pub fn lf_clk(&self) -> u32 {
+        self.lf
     }
-    pub fn create_clock(&self, freq: u32) -> DerivedClock {
-        //config clock and return.
-        let _ = freq;
-        DerivedClock {}
+    /// ext_clk returns the EXTCLK frequency, 0 when there is no
+    /// external clock.
+    #[inline(always)]
+    pub fn ext_clk(&self) -> u32 {
+        self.ext
     }
-    pub fn create_cm0_clock(&self) {
-        todo!()
+    /// path returns a CLK_PATH frequency.
+    #[inline(always)]
+    pub fn path(&self, path: ClockPath) -> u32 {
+        self.paths[path as usize]
+    }
+    /// hf returns a CLK_HF root frequency, 0 when the root is disabled.
+    #[inline(always)]
+    pub fn hf(&self, root: RootClocks) -> u32 {
+        self.hf[root as usize]
+    }
+    /// cm4_clk returns CLK_FAST, the CM4 core clock.
+    #[inline(always)]
+    pub fn cm4_clk(&self) -> u32 {
+        self.fast
+    }
+    /// peri_clk returns CLK_PERI which clocks the peripheral dividers.
+    #[inline(always)]
+    pub fn peri_clk(&self) -> u32 {
+        self.peri
+    }
+    /// cm0_clk returns CLK_SLOW, the CM0+ core clock.
+    #[inline(always)]
+    pub fn cm0_clk(&self) -> u32 {
+        self.slow
+    }
+    /// core_clk returns the clock of the core the code is built for.
+    #[cfg(armv6m)]
+    #[inline(always)]
+    pub fn core_clk(&self) -> u32 {
+        self.slow
+    }
+    /// core_clk returns the clock of the core the code is built for.
+    #[cfg(not(armv6m))]
+    #[inline(always)]
+    pub fn core_clk(&self) -> u32 {
+        self.fast
+    }
+}
+
+impl Psoc {
+    /// start_system_clocks applies the clock configuration and returns
+    /// the record of the resulting frequencies.
+    pub fn start_system_clocks(&self, config: ClockConfig) -> Result<Clocks, Error> {
+        config.freeze(&self.system, &self.cpuss, &self.flash)
     }
-    pub fn create_cm4_clock(&self) {
-        todo!()
+    /// create_clock derives a clock of freq from CLK_PERI using one of
+    /// the dividers from Psoc::dividers.
+    pub fn create_clock<D: PeriClockDivider>(
+        &self,
+        divider: D,
+        clocks: &Clocks,
+        freq: u32,
+    ) -> Result<DerivedClock<D>, Error> {
+        DerivedClock::new(divider, clocks, freq)
     }
 }
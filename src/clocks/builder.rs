@@ -0,0 +1,206 @@
+//! builder.rs implements a type-state clock builder. Where ClockConfig
+//! accepts settings in any order and checks them in freeze, the
+//! ClockTreeBuilder follows the trm 20.3 sequence through its types:
+//! 1. Sources: LFCLK, ECO and the path sources.
+//! 2. Paths: the FLL on CLK_PATH0 and the PLL on CLK_PATH1.
+//! 3. Roots: CLK_HF0 must be given to enter this stage, then the
+//!    other CLK_HF roots.
+//! 4. Ready: the CPU dividers have been set and the whole tree checked
+//!    against the SystemMode limits.
+//! Each setting is checked as it is made so an invalid tree is reported
+//! where it is built, and only a Ready builder can be applied.
+//!```
+//! let clocks = ClockTreeBuilder::new(SystemMode::Lp)
+//!     .eco(EcoConfig::new(17_203_200, 18, 50, 100)?)
+//!     .path_source(ClockPath::Path1, PathSource::Eco)?
+//!     .paths()
+//!     .fll(100_000_000)?
+//!     .pll(150_000_000)?
+//!     .roots(ClockPath::Path1, Divider::NoDiv)?
+//!     .hf_root(RootClocks::Smif, ClockPath::Path0, Divider::Div2)?
+//!     .dividers(0, 1, 0)?
+//!     .apply(&system, &cpuss, &flash)?;
+//!```
+
+use crate::clocks::{
+    ClockConfig, ClockSource, Clocks, ExtClk, FLL_REFERENCE_TOLERANCE_PPM, LP_MAX_HF_FREQ,
+    ULP_MAX_HF_FREQ,
+};
+use crate::drivers::cpuss::Cpuss;
+use crate::drivers::flashc::Flash;
+use crate::drivers::system::{
+    ClockPath, Divider, EcoConfig, FllConfig, PathSource, PllConfig, RootClocks, System,
+};
+use crate::error::Error;
+use crate::psoc::SystemMode;
+
+/// Sources is the stage selecting LFCLK, the ECO and the path sources.
+pub struct Sources;
+/// Paths is the stage configuring the FLL and PLL.
+pub struct Paths;
+/// Roots is the stage selecting the CLK_HF roots, CLK_HF0 is set.
+pub struct Roots;
+/// Ready is the final stage, the tree is valid and can be applied.
+pub struct Ready {
+    clocks: Clocks,
+}
+
+/// ClockTreeBuilder is a clock configuration in the stage S.
+pub struct ClockTreeBuilder<S> {
+    config: ClockConfig,
+    paths: [u32; 5],
+    stage: S,
+}
+
+impl<S> ClockTreeBuilder<S> {
+    #[inline(always)]
+    fn into_stage<T>(self, stage: T) -> ClockTreeBuilder<T> {
+        ClockTreeBuilder {
+            config: self.config,
+            paths: self.paths,
+            stage,
+        }
+    }
+    /// max_hf returns the CLK_HF limit of the SystemMode.
+    #[inline(always)]
+    fn max_hf(&self) -> u32 {
+        match self.config.system_mode {
+            SystemMode::Ulp => ULP_MAX_HF_FREQ,
+            _ => LP_MAX_HF_FREQ,
+        }
+    }
+    /// check_root returns an error when a root from path divided by div
+    /// would exceed the SystemMode limit.
+    fn check_root(&self, path: ClockPath, div: Divider) -> Result<(), Error> {
+        match self.paths[path as usize] >> (div as u32) > self.max_hf() {
+            true => Err(Error::ClockFrequencyExceedsSystemModeLimit),
+            false => Ok(()),
+        }
+    }
+}
+
+impl ClockTreeBuilder<Sources> {
+    /// new starts a clock tree for the SystemMode with the ILO for
+    /// LFCLK, the IMO on every path and no FLL, PLL or CLK_HF roots.
+    pub fn new(system_mode: SystemMode) -> Self {
+        let mut config = ClockConfig::new().system_mode(system_mode).no_fll();
+        config.hf_roots = [None; 5];
+        ClockTreeBuilder {
+            config,
+            paths: [0; 5],
+            stage: Sources,
+        }
+    }
+    /// lfclk selects the LFCLK source, one of Ilo, Wco or Pilo.
+    pub fn lfclk(mut self, source: ClockSource) -> Result<Self, Error> {
+        match source {
+            ClockSource::Ilo | ClockSource::Wco | ClockSource::Pilo => {
+                self.config = self.config.lfclk(source);
+                Ok(self)
+            }
+            _ => Err(Error::UnknownLfClkSource),
+        }
+    }
+    /// lfclk_freq records a measured LFCLK frequency, see ClockConfig::lfclk_freq.
+    pub fn lfclk_freq(mut self, lfclk_hz: u32) -> Self {
+        self.config = self.config.lfclk_freq(lfclk_hz);
+        self
+    }
+    /// eco starts the ECO when applied, it must be set before a path
+    /// selects PathSource::Eco.
+    pub fn eco(mut self, eco_config: EcoConfig) -> Self {
+        self.config = self.config.eco(eco_config);
+        self
+    }
+    /// ext_clk records the external clock, it must be set before a path
+    /// selects PathSource::ExtClk.
+    pub fn ext_clk(mut self, ext_clk: &ExtClk) -> Self {
+        self.config = self.config.ext_clk(ext_clk);
+        self
+    }
+    /// path_source selects the source of a path. It returns an error
+    /// when the source has no known frequency, i.e. the ECO or EXTCLK
+    /// has not been set or the source is AltHf or DsiMux.
+    pub fn path_source(mut self, path: ClockPath, source: PathSource) -> Result<Self, Error> {
+        self.config = self.config.path_source(path, source);
+        self.config.path_source_freq(path as usize)?;
+        Ok(self)
+    }
+    /// paths ends the source selection.
+    pub fn paths(mut self) -> ClockTreeBuilder<Paths> {
+        for path in 0..self.paths.len() {
+            // Every source was checked by path_source.
+            self.paths[path] = self.config.path_source_freq(path).unwrap_or(0);
+        }
+        self.into_stage(Paths)
+    }
+}
+
+impl ClockTreeBuilder<Paths> {
+    /// fll sets the FLL output on CLK_PATH0, 24-100MHz. It returns an
+    /// error when the FLL cannot reach target_hz from the path source.
+    pub fn fll(mut self, target_hz: u32) -> Result<Self, Error> {
+        let reference = self.paths[ClockPath::Path0 as usize];
+        let fll_config = FllConfig::for_target(reference, target_hz, FLL_REFERENCE_TOLERANCE_PPM)?;
+        self.paths[ClockPath::Path0 as usize] = fll_config.output_freq(reference);
+        self.config = self.config.fll(target_hz);
+        Ok(self)
+    }
+    /// pll sets the PLL output on CLK_PATH1, 10.625-150MHz. It returns
+    /// an error when the PLL cannot reach target_hz from the path source.
+    pub fn pll(mut self, target_hz: u32) -> Result<Self, Error> {
+        let reference = self.paths[ClockPath::Path1 as usize];
+        let pll_config = PllConfig::for_target(reference, target_hz)?;
+        self.paths[ClockPath::Path1 as usize] = pll_config.output_freq(reference);
+        self.config = self.config.pll(target_hz);
+        Ok(self)
+    }
+    /// roots ends the path configuration by selecting CLK_HF0, the root
+    /// of the CPUs and PERI, which every tree needs.
+    pub fn roots(mut self, path: ClockPath, div: Divider) -> Result<ClockTreeBuilder<Roots>, Error> {
+        self.check_root(path, div)?;
+        self.config = self.config.hf_root(RootClocks::CpuPeriAhb, path, div);
+        Ok(self.into_stage(Roots))
+    }
+}
+
+impl ClockTreeBuilder<Roots> {
+    /// hf_root enables a CLK_HF root clock from a path with a divider.
+    /// It returns an error when the root would exceed the SystemMode limit.
+    pub fn hf_root(mut self, root: RootClocks, path: ClockPath, div: Divider) -> Result<Self, Error> {
+        self.check_root(path, div)?;
+        self.config = self.config.hf_root(root, path, div);
+        Ok(self)
+    }
+    /// dividers sets the CPU dividers and checks the complete tree:
+    ///  - CLK_FAST = CLK_HF0 / (fast_div + 1)
+    ///  - CLK_PERI = CLK_HF0 / (peri_div + 1)
+    ///  - CLK_SLOW = CLK_PERI / (slow_div + 1)
+    pub fn dividers(
+        mut self,
+        fast_div: u8,
+        peri_div: u8,
+        slow_div: u8,
+    ) -> Result<ClockTreeBuilder<Ready>, Error> {
+        self.config = self
+            .config
+            .cm4_fast_div(fast_div)
+            .peri_div(peri_div)
+            .cm0_slow_div(slow_div);
+        let clocks = self.config.calculate()?;
+        Ok(self.into_stage(Ready { clocks }))
+    }
+}
+
+impl ClockTreeBuilder<Ready> {
+    /// clocks returns the frequencies the tree will run at once applied.
+    #[inline(always)]
+    pub fn clocks(&self) -> Clocks {
+        self.stage.clocks
+    }
+    /// apply writes the whole tree to the hardware in one step, see
+    /// ClockConfig::freeze for the sequence.
+    pub fn apply(self, system: &System, cpuss: &Cpuss, flash: &Flash) -> Result<Clocks, Error> {
+        self.config.freeze(system, cpuss, flash)
+    }
+}
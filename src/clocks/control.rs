@@ -0,0 +1,268 @@
+//! control.rs implements runtime changes of the system frequency.
+//! ClockControl owns the applied ClockConfig and the resulting Clocks
+//! record. set_system_frequency retargets the FLL or PLL feeding
+//! CLK_HF0 and set_system_mode moves between the Lp and Ulp modes. Only
+//! the loop feeding CLK_HF0 is touched: it is bypassed to its reference,
+//! reconfigured and relocked, the sources and other paths keep running.
+//! Both order the LDO and wait state changes around the frequency change:
+//! - Wait states are raised before and reduced after the change.
+//! - Ulp to Lp: the LDO is raised before the frequency.
+//! - Lp to Ulp: the frequency is lowered and the Ulp wait states set
+//!   before the LDO.
+//! Registered listeners are called before the change, with the current
+//! and new records, and after it, so drivers clocked from CLK_HF0 can
+//! pause and recompute their dividers.
+//!```
+//! fn uart_clock_listener(change: &ClockChange) {
+//!     match change {
+//!         ClockChange::Before { .. } => { /* wait for the tx fifo to empty */ }
+//!         ClockChange::After { clocks } => { /* recompute the baud divider */ }
+//!         ClockChange::Failed => { /* reapply a known configuration */ }
+//!     }
+//! }
+//! let mut clock_control: ClockControl<4> = ClockControl::new(ClockConfig::new(), &system, &cpuss, &flash)?;
+//! clock_control.register(uart_clock_listener)?;
+//! let clocks = clock_control.set_system_frequency(50_000_000, &system, &cpuss, &flash)?;
+//!```
+
+use crate::clocks::{
+    ClockConfig, Clocks, FLL_REFERENCE_TOLERANCE_PPM, FLL_STARTUP_TIMEOUT_US, PLL_STARTUP_TIMEOUT_US,
+};
+use crate::drivers::cpuss::Cpuss;
+use crate::drivers::flashc::Flash;
+use crate::drivers::system::{ClockPath, FllConfig, PllConfig, RootClocks, System};
+use crate::error::Error;
+use crate::psoc::SystemMode;
+
+/// ClockChange is passed to the listeners around a frequency change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockChange {
+    /// Before is sent while the hardware still runs at from.
+    Before { from: Clocks, to: Clocks },
+    /// After is sent once the hardware runs at clocks. When a change
+    /// fails the previous configuration is restored and After carries
+    /// the previous record.
+    After { clocks: Clocks },
+    /// Failed is sent instead of After when a change failed and the
+    /// previous configuration could not be restored either, the
+    /// frequencies are then unknown.
+    Failed,
+}
+
+/// ClockListener is called around each frequency change. Listeners are
+/// called in registration order with interrupts enabled.
+pub type ClockListener = fn(&ClockChange);
+
+/// ClockControl holds the applied clock configuration and up to N
+/// registered listeners.
+pub struct ClockControl<const N: usize> {
+    config: ClockConfig,
+    clocks: Clocks,
+    listeners: [Option<ClockListener>; N],
+}
+
+impl<const N: usize> ClockControl<N> {
+    /// new applies the configuration, see ClockConfig::freeze.
+    pub fn new(config: ClockConfig, system: &System, cpuss: &Cpuss, flash: &Flash) -> Result<Self, Error> {
+        let clocks = config.freeze(system, cpuss, flash)?;
+        Ok(ClockControl {
+            config,
+            clocks,
+            listeners: [None; N],
+        })
+    }
+    /// clocks returns the record of the current frequencies.
+    #[inline(always)]
+    pub fn clocks(&self) -> Clocks {
+        self.clocks
+    }
+    /// register adds a listener, it returns an error when all N slots
+    /// are in use.
+    pub fn register(&mut self, listener: ClockListener) -> Result<(), Error> {
+        match self.listeners.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(listener);
+                Ok(())
+            }
+            None => Err(Error::ClockListenersFull),
+        }
+    }
+    /// unregister removes a listener.
+    pub fn unregister(&mut self, listener: ClockListener) -> () {
+        for slot in self.listeners.iter_mut() {
+            if *slot == Some(listener) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// set_system_frequency sets CLK_HF0 to target_hz by retargeting
+    /// the FLL or PLL on the path CLK_HF0 is selected from, the CLK_HF0
+    /// divider and the CPU dividers are kept.
+    /// It returns an error when CLK_HF0 is not on CLK_PATH0 or CLK_PATH1,
+    /// or the new tree is invalid, in which case nothing is changed.
+    pub fn set_system_frequency(
+        &mut self,
+        target_hz: u32,
+        system: &System,
+        cpuss: &Cpuss,
+        flash: &Flash,
+    ) -> Result<Clocks, Error> {
+        let config = self.retarget(target_hz)?;
+        self.switch(config, system, cpuss, flash)
+    }
+    /// set_system_mode changes between the Lp and Ulp modes and sets
+    /// CLK_HF0 to target_hz, which must be within the limits of mode.
+    pub fn set_system_mode(
+        &mut self,
+        mode: SystemMode,
+        target_hz: u32,
+        system: &System,
+        cpuss: &Cpuss,
+        flash: &Flash,
+    ) -> Result<Clocks, Error> {
+        let config = self.retarget(target_hz)?.system_mode(mode);
+        self.switch(config, system, cpuss, flash)
+    }
+
+    /// retarget returns the current configuration with the FLL or PLL
+    /// feeding CLK_HF0 set for target_hz.
+    fn retarget(&self, target_hz: u32) -> Result<ClockConfig, Error> {
+        let (path, div) = self.config.hf_roots[RootClocks::CpuPeriAhb as usize]
+            .ok_or(Error::ClockHf0NotConfigured)?;
+        let path_hz = target_hz << (div as u32);
+        match path {
+            ClockPath::Path0 => Ok(self.config.fll(path_hz)),
+            ClockPath::Path1 => Ok(self.config.pll(path_hz)),
+            _ => Err(Error::SystemFrequencyCannotBeSet),
+        }
+    }
+
+    /// switch moves the hardware to config and notifies the listeners.
+    /// On an error the previous loop configuration, LDO and wait states
+    /// are restored. When the restore fails too it returns
+    /// ClockRestoreFailed, the clocks record is then invalid and the
+    /// configuration must be applied again with new.
+    fn switch(&mut self, config: ClockConfig, system: &System, cpuss: &Cpuss, flash: &Flash) -> Result<Clocks, Error> {
+        let to = config.calculate()?;
+        // Solve both loops before any change, so the restore cannot fail
+        // to solve.
+        LoopConfig::for_hf0(&config)?;
+        LoopConfig::for_hf0(&self.config)?;
+        self.notify(&ClockChange::Before {
+            from: self.clocks,
+            to,
+        });
+        let result = apply(&self.config, &self.clocks, &config, &to, system, cpuss, flash);
+        match result {
+            Ok(()) => {
+                self.config = config;
+                self.clocks = to;
+            }
+            Err(_) => {
+                if apply(&config, &to, &self.config, &self.clocks, system, cpuss, flash).is_err() {
+                    self.notify(&ClockChange::Failed);
+                    return Err(Error::ClockRestoreFailed);
+                }
+            }
+        }
+        self.notify(&ClockChange::After {
+            clocks: self.clocks,
+        });
+        result.map(|_| self.clocks)
+    }
+
+    fn notify(&self, change: &ClockChange) -> () {
+        for listener in self.listeners.iter().flatten() {
+            listener(change);
+        }
+    }
+}
+
+/// LoopConfig is the FLL or PLL configuration of the loop feeding
+/// CLK_HF0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LoopConfig {
+    Fll(FllConfig),
+    Pll(PllConfig),
+}
+
+impl LoopConfig {
+    /// for_hf0 solves the loop on the path CLK_HF0 is selected from.
+    fn for_hf0(config: &ClockConfig) -> Result<Self, Error> {
+        let (path, _) = config.hf_roots[RootClocks::CpuPeriAhb as usize]
+            .ok_or(Error::ClockHf0NotConfigured)?;
+        match (path, config.fll_hz, config.pll_hz) {
+            (ClockPath::Path0, Some(target), _) => {
+                let reference = config.path_source_freq(ClockPath::Path0 as usize)?;
+                FllConfig::for_target(reference, target, FLL_REFERENCE_TOLERANCE_PPM).map(LoopConfig::Fll)
+            }
+            (ClockPath::Path1, _, Some(target)) => {
+                let reference = config.path_source_freq(ClockPath::Path1 as usize)?;
+                PllConfig::for_target(reference, target).map(LoopConfig::Pll)
+            }
+            _ => Err(Error::SystemFrequencyCannotBeSet),
+        }
+    }
+    /// relock switches the path to the loop reference, reconfigures the
+    /// loop and waits for the lock before switching back to its output.
+    fn relock(self, system: &System) -> Result<(), Error> {
+        match self {
+            LoopConfig::Fll(fll_config) => {
+                system.configure_fll(fll_config);
+                system.start_fll(FLL_STARTUP_TIMEOUT_US)
+            }
+            LoopConfig::Pll(pll_config) => {
+                system.configure_pll(pll_config);
+                system.start_pll(PLL_STARTUP_TIMEOUT_US)
+            }
+        }
+    }
+}
+
+/// apply changes CLK_HF0 from the from configuration to the to
+/// configuration, which differ only in the loop feeding CLK_HF0 and the
+/// system mode. The sources, the other paths and roots are left running.
+/// The wait states are raised to the larger of the counts needed before
+/// and after the change, each in its own mode, and reduced after it:
+/// - Ulp to Lp: the LDO is raised before the loop is retargeted.
+/// - Lp to Ulp: the loop is retargeted, then the Ulp wait states set,
+///   then the LDO lowered.
+fn apply(
+    from: &ClockConfig,
+    from_clocks: &Clocks,
+    to: &ClockConfig,
+    to_clocks: &Clocks,
+    system: &System,
+    cpuss: &Cpuss,
+    flash: &Flash,
+) -> Result<(), Error> {
+    let target = LoopConfig::for_hf0(to)?;
+    let from_hf0 = from_clocks.hf(RootClocks::CpuPeriAhb);
+    let to_hf0 = to_clocks.hf(RootClocks::CpuPeriAhb);
+    cpuss.set_wait_states(
+        Cpuss::wait_states(from_hf0, &from.system_mode).max(Cpuss::wait_states(to_hf0, &to.system_mode)),
+    );
+    flash.set_wait_states(
+        Flash::wait_states(from_hf0, &from.system_mode).max(Flash::wait_states(to_hf0, &to.system_mode)),
+    );
+    if from.system_mode == SystemMode::Ulp && to.system_mode == SystemMode::Lp {
+        system.configure_ldo_mode(&SystemMode::Lp);
+        flash.configure_ldo_mode(&SystemMode::Lp);
+    }
+
+    target.relock(system)?;
+    if let Some((_, div)) = to.hf_roots[RootClocks::CpuPeriAhb as usize] {
+        system.configure_root_clk_div(RootClocks::CpuPeriAhb, div);
+    }
+    cpuss.configure_clocks_cm4(to.fast_div);
+    cpuss.configure_clocks_cm0(to.peri_div, to.slow_div);
+
+    cpuss.configure_wait_states(to_hf0, &to.system_mode);
+    flash.configure_wait_states(to_hf0, &to.system_mode);
+    if from.system_mode == SystemMode::Lp && to.system_mode == SystemMode::Ulp {
+        system.configure_ldo_mode(&SystemMode::Ulp);
+        flash.configure_ldo_mode(&SystemMode::Ulp);
+    }
+    Ok(())
+}
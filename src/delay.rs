@@ -1,86 +1,183 @@
 //! Delays
-
+//!
+//! Delay blocks for a time measured with a DelayTimer, by default the
+//! SysTick of the core the code runs on. Delays are calculated from the
+//! timer tick rate in 64 bit arithmetic so delays of any length are
+//! exact to a tick and never overflow. When the SysTick is used
+//! elsewhere, e.g. by the Timebase, a TCPWM counter can be used instead.
+//!```
+//! let mut delay = Delay::new(cp.SYST, &clocks);
+//! delay.delay_ms(1_500u32);
+//! // or on a TCPWM counter
+//! let mut delay = Delay::with_timer(Timer::new(counters.counter0, &dividers.div16_0, Prescaler::Div1));
+//!```
+
+use crate::clocks::Clocks;
 use crate::ehal::blocking::delay::{DelayMs, DelayUs};
+use crate::ehal1::delay::DelayNs;
+use crate::error::Error;
 use cast::u32;
 use cortex_m::peripheral::syst::SystClkSource;
 use cortex_m::peripheral::SYST;
 
-// TODO: Verify this value.
-const FREQ: u32 = 150_000_000;
+/// DelayTimer is a down or up counter a Delay can block on.
+pub trait DelayTimer {
+    /// tick_hz returns the counter clock rate.
+    fn tick_hz(&self) -> u32;
+    /// max_ticks returns the longest wait_ticks supported.
+    fn max_ticks(&self) -> u32;
+    /// wait_ticks blocks for 1..=max_ticks counter ticks.
+    fn wait_ticks(&mut self, ticks: u32);
+}
 
-/// System timer (SysTick) as a delay provider
-pub struct Delay {
+/// SystTimer is the system timer (SysTick) as a DelayTimer.
+pub struct SystTimer {
     syst: SYST,
+    tick_hz: u32,
 }
 
-impl Delay {
-    /// Configures the system timer (SysTick) as a delay provider
-    pub fn new(mut syst: SYST) -> Self {
+impl SystTimer {
+    /// core clocks the SysTick from the core clock of the core the code
+    /// is built for, CLK_SLOW on the CM0+ and CLK_FAST on the CM4.
+    pub fn core(mut syst: SYST, clocks: &Clocks) -> Self {
         syst.set_clock_source(SystClkSource::Core);
-
-        Delay { syst }
+        SystTimer {
+            syst,
+            tick_hz: clocks.core_clk(),
+        }
+    }
+    /// external clocks the SysTick from the external clock selected by
+    /// Cpuss::configure_systick_source, source_hz is its frequency, e.g.
+    /// Clocks::lf_clk.
+    /// It returns an error when no external clock is available,
+    /// SYST_CALIB.NOREF.
+    pub fn external(mut syst: SYST, source_hz: u32) -> Result<Self, Error> {
+        if !SYST::has_reference_clock() {
+            return Err(Error::SystickReferenceNotAvailable);
+        }
+        syst.set_clock_source(SystClkSource::External);
+        Ok(SystTimer {
+            syst,
+            tick_hz: source_hz,
+        })
     }
-
     /// Releases the system timer (SysTick) resource
     pub fn free(self) -> SYST {
         self.syst
     }
 }
 
-impl DelayMs<u32> for Delay {
-    fn delay_ms(&mut self, ms: u32) {
-        self.delay_us(ms * 1_000);
+impl DelayTimer for SystTimer {
+    #[inline(always)]
+    fn tick_hz(&self) -> u32 {
+        self.tick_hz
+    }
+    #[inline(always)]
+    fn max_ticks(&self) -> u32 {
+        // The SysTick Reload Value register supports values between 1 and 0x00FFFFFF.
+        0x0100_0000
+    }
+    fn wait_ticks(&mut self, ticks: u32) {
+        self.syst.set_reload(ticks.max(2) - 1);
+        self.syst.clear_current();
+        self.syst.enable_counter();
+        while !self.syst.has_wrapped() {}
+        self.syst.disable_counter();
     }
 }
 
-impl DelayMs<u16> for Delay {
-    fn delay_ms(&mut self, ms: u16) {
-        self.delay_ms(u32(ms));
+/// Delay is a delay provider on a DelayTimer, the system timer
+/// (SysTick) by default.
+pub struct Delay<T: DelayTimer = SystTimer> {
+    timer: T,
+}
+
+impl Delay<SystTimer> {
+    /// Configures the system timer (SysTick) as a delay provider
+    /// clocked by the core clock from the frozen Clocks.
+    pub fn new(syst: SYST, clocks: &Clocks) -> Self {
+        Delay {
+            timer: SystTimer::core(syst, clocks),
+        }
+    }
+
+    /// Releases the system timer (SysTick) resource
+    pub fn free(self) -> SYST {
+        self.timer.free()
     }
 }
 
-impl DelayMs<u8> for Delay {
-    fn delay_ms(&mut self, ms: u8) {
-        self.delay_ms(u32(ms));
+impl<T: DelayTimer> Delay<T> {
+    /// with_timer uses any DelayTimer as the delay provider.
+    pub fn with_timer(timer: T) -> Self {
+        Delay { timer }
+    }
+    /// release returns the timer.
+    pub fn release(self) -> T {
+        self.timer
+    }
+    /// delay_ticks waits for a number of timer ticks in chunks of at
+    /// most max_ticks.
+    fn delay_ticks(&mut self, mut ticks: u64) {
+        let max_ticks = self.timer.max_ticks() as u64;
+        while ticks != 0 {
+            let current = ticks.min(max_ticks);
+            self.timer.wait_ticks(current as u32);
+            ticks -= current;
+        }
+    }
+    /// delay_period waits for count periods of 1 / per_second seconds,
+    /// rounding up to a whole tick.
+    fn delay_period(&mut self, count: u64, per_second: u64) {
+        let tick_hz = self.timer.tick_hz() as u64;
+        self.delay_ticks((count * tick_hz + per_second - 1) / per_second);
     }
 }
 
-impl DelayUs<u32> for Delay {
+impl<T: DelayTimer> DelayNs for Delay<T> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.delay_period(ns as u64, 1_000_000_000);
+    }
     fn delay_us(&mut self, us: u32) {
-        // The SysTick Reload Value register supports values between 1 and 0x00FFFFFF.
-        const MAX_RVR: u32 = 0x00FF_FFFF;
-
-        let mut total_rvr = us * (FREQ / 8_000_000);
-
-        while total_rvr != 0 {
-            let current_rvr = if total_rvr <= MAX_RVR {
-                total_rvr
-            } else {
-                MAX_RVR
-            };
+        self.delay_period(us as u64, 1_000_000);
+    }
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay_period(ms as u64, 1_000);
+    }
+}
 
-            self.syst.set_reload(current_rvr);
-            self.syst.clear_current();
-            self.syst.enable_counter();
+impl<T: DelayTimer> DelayMs<u32> for Delay<T> {
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay_period(ms as u64, 1_000);
+    }
+}
 
-            // Update the tracking variable while we are waiting...
-            total_rvr -= current_rvr;
+impl<T: DelayTimer> DelayMs<u16> for Delay<T> {
+    fn delay_ms(&mut self, ms: u16) {
+        DelayMs::delay_ms(self, u32(ms));
+    }
+}
 
-            while !self.syst.has_wrapped() {}
+impl<T: DelayTimer> DelayMs<u8> for Delay<T> {
+    fn delay_ms(&mut self, ms: u8) {
+        DelayMs::delay_ms(self, u32(ms));
+    }
+}
 
-            self.syst.disable_counter();
-        }
+impl<T: DelayTimer> DelayUs<u32> for Delay<T> {
+    fn delay_us(&mut self, us: u32) {
+        self.delay_period(us as u64, 1_000_000);
     }
 }
 
-impl DelayUs<u16> for Delay {
+impl<T: DelayTimer> DelayUs<u16> for Delay<T> {
     fn delay_us(&mut self, us: u16) {
-        self.delay_us(u32(us))
+        DelayUs::delay_us(self, u32(us))
     }
 }
 
-impl DelayUs<u8> for Delay {
+impl<T: DelayTimer> DelayUs<u8> for Delay<T> {
     fn delay_us(&mut self, us: u8) {
-        self.delay_us(u32(us))
+        DelayUs::delay_us(self, u32(us))
     }
 }
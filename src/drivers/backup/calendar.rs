@@ -0,0 +1,596 @@
+//! calendar.rs implements the calendar and daylight saving time (DST)
+//! arithmetic used with the RTC. It is pure, with no register access,
+//! so it can be checked on the host.
+//! The RTC counts years 2000 to 2099 in two BCD digits, every fourth
+//! year in that range is a leap year.
+
+#![deny(unsafe_code)]
+
+use crate::error::Error;
+
+/// RTC_BASE_YEAR is the year of RTC_YEAR 0.
+pub const RTC_BASE_YEAR: u16 = 2000;
+/// RTC_MAX_YEAR is the year of RTC_YEAR 99.
+pub const RTC_MAX_YEAR: u16 = 2099;
+
+const SECONDS_PER_DAY: u32 = 86_400;
+// Days before each month in a common year.
+const DAYS_BEFORE_MONTH: [u16; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+
+/// Weekday is the RTC_DAY encoding of the day of the week.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weekday {
+    Sunday = 1,
+    Monday = 2,
+    Tuesday = 3,
+    Wednesday = 4,
+    Thursday = 5,
+    Friday = 6,
+    Saturday = 7,
+}
+
+impl Weekday {
+    /// from_number returns the weekday of an RTC_DAY value, 1..=7.
+    pub fn from_number(n: u8) -> Option<Weekday> {
+        match n {
+            1 => Some(Weekday::Sunday),
+            2 => Some(Weekday::Monday),
+            3 => Some(Weekday::Tuesday),
+            4 => Some(Weekday::Wednesday),
+            5 => Some(Weekday::Thursday),
+            6 => Some(Weekday::Friday),
+            7 => Some(Weekday::Saturday),
+            _ => None,
+        }
+    }
+}
+
+/// is_leap_year returns true for the Gregorian leap years.
+#[inline(always)]
+pub fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// days_in_month returns the number of days in a month, 1..=12, 0 for
+/// an invalid month.
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// weekday returns the day of the week of a valid date.
+pub fn weekday(year: u16, month: u8, day: u8) -> Weekday {
+    // 2000-01-01 was a Saturday.
+    let days = days_since_base(year, month, day);
+    // Valid as (days + 6) % 7 is 0..=6.
+    Weekday::from_number(((days + 6) % 7) as u8 + 1).unwrap_or(Weekday::Sunday)
+}
+
+/// days_since_base returns the days from 2000-01-01 to a valid date.
+fn days_since_base(year: u16, month: u8, day: u8) -> u32 {
+    let years = (year - RTC_BASE_YEAR) as u32;
+    // Leap years before this year, 2000 is one.
+    let leap_days = match years {
+        0 => 0,
+        y => (y - 1) / 4 + 1 - (y - 1) / 100 + (y - 1) / 400,
+    };
+    let mut days = years * 365 + leap_days;
+    days += DAYS_BEFORE_MONTH[(month - 1) as usize] as u32;
+    if month > 2 && is_leap_year(year) {
+        days += 1;
+    }
+    days + day as u32 - 1
+}
+
+/// to_bcd returns the two BCD digits of a value, 0..=99.
+#[inline(always)]
+pub fn to_bcd(value: u8) -> u8 {
+    (value / 10) << 4 | value % 10
+}
+
+/// from_bcd returns the value of two BCD digits.
+#[inline(always)]
+pub fn from_bcd(bcd: u8) -> u8 {
+    (bcd >> 4) * 10 + (bcd & 0xf)
+}
+
+/// to_12_hour returns the 12-hour clock hour, 1..=12, and true for PM.
+#[inline(always)]
+pub fn to_12_hour(hour: u8) -> (u8, bool) {
+    match hour % 12 {
+        0 => (12, hour >= 12),
+        h => (h, hour >= 12),
+    }
+}
+
+/// from_12_hour returns the 24-hour clock hour of a 12-hour clock hour.
+#[inline(always)]
+pub fn from_12_hour(hour: u8, pm: bool) -> u8 {
+    hour % 12 + if pm { 12 } else { 0 }
+}
+
+/// DateTime is a date and time in binary, 24-hour clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// new returns a DateTime, or an error when a field is out of range
+    /// or the year is outside 2000..=2099.
+    pub fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<Self, Error> {
+        if year < RTC_BASE_YEAR
+            || year > RTC_MAX_YEAR
+            || month == 0
+            || month > 12
+            || day == 0
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return Err(Error::RtcInvalidDateTime);
+        }
+        Ok(DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+    /// weekday returns the day of the week.
+    #[inline(always)]
+    pub fn weekday(&self) -> Weekday {
+        weekday(self.year, self.month, self.day)
+    }
+    /// to_seconds returns the seconds since 2000-01-01 00:00:00.
+    pub fn to_seconds(&self) -> u32 {
+        days_since_base(self.year, self.month, self.day) * SECONDS_PER_DAY
+            + self.hour as u32 * 3600
+            + self.minute as u32 * 60
+            + self.second as u32
+    }
+    /// from_seconds returns the DateTime seconds after 2000-01-01
+    /// 00:00:00, saturating at the end of 2099.
+    pub fn from_seconds(seconds: u32) -> Self {
+        let max = DateTime {
+            year: RTC_MAX_YEAR,
+            month: 12,
+            day: 31,
+            hour: 23,
+            minute: 59,
+            second: 59,
+        };
+        if seconds > max.to_seconds() {
+            return max;
+        }
+        let mut days = seconds / SECONDS_PER_DAY;
+        let time = seconds % SECONDS_PER_DAY;
+        let mut year = RTC_BASE_YEAR;
+        loop {
+            let year_days = if is_leap_year(year) { 366 } else { 365 };
+            if days < year_days {
+                break;
+            }
+            days -= year_days;
+            year += 1;
+        }
+        let mut month = 1;
+        while days >= days_in_month(year, month) as u32 {
+            days -= days_in_month(year, month) as u32;
+            month += 1;
+        }
+        DateTime {
+            year,
+            month,
+            day: days as u8 + 1,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        }
+    }
+    /// add_seconds returns the DateTime moved by a number of seconds,
+    /// saturating at the RTC range.
+    pub fn add_seconds(&self, seconds: i32) -> Self {
+        let moved = self.to_seconds() as i64 + seconds as i64;
+        DateTime::from_seconds(moved.max(0).min(u32::MAX as i64) as u32)
+    }
+}
+
+/// BcdDateTime holds the RTC register fields in BCD, 24-hour clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BcdDateTime {
+    /// year is the two BCD digits of the year after 2000.
+    pub year: u8,
+    pub month: u8,
+    pub day: u8,
+    pub weekday: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl From<&DateTime> for BcdDateTime {
+    fn from(date_time: &DateTime) -> Self {
+        BcdDateTime {
+            year: to_bcd((date_time.year - RTC_BASE_YEAR) as u8),
+            month: to_bcd(date_time.month),
+            day: to_bcd(date_time.day),
+            weekday: date_time.weekday() as u8,
+            hour: to_bcd(date_time.hour),
+            minute: to_bcd(date_time.minute),
+            second: to_bcd(date_time.second),
+        }
+    }
+}
+
+impl BcdDateTime {
+    /// to_date_time returns the binary DateTime, or an error when a
+    /// field is not valid BCD or out of range. The weekday is not
+    /// checked, it follows from the date.
+    pub fn to_date_time(&self) -> Result<DateTime, Error> {
+        let fields = [
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        ];
+        if fields.iter().any(|bcd| bcd & 0xf > 9 || bcd >> 4 > 9) {
+            return Err(Error::RtcInvalidDateTime);
+        }
+        DateTime::new(
+            RTC_BASE_YEAR + from_bcd(self.year) as u16,
+            from_bcd(self.month),
+            from_bcd(self.day),
+            from_bcd(self.hour),
+            from_bcd(self.minute),
+            from_bcd(self.second),
+        )
+    }
+}
+
+/// WeekOfMonth selects the occurrence of a weekday in a month.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeekOfMonth {
+    First = 0,
+    Second = 1,
+    Third = 2,
+    Fourth = 3,
+    Last = 4,
+}
+
+/// DstTransition is a DST change, e.g. the last Sunday of March at
+/// 01:00. The hour is in local time as the clock reads it before the
+/// change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DstTransition {
+    pub month: u8,
+    pub week: WeekOfMonth,
+    pub weekday: Weekday,
+    pub hour: u8,
+}
+
+impl DstTransition {
+    /// date_time returns the local time of the transition in a year.
+    pub fn date_time(&self, year: u16) -> DateTime {
+        let first = weekday(year, self.month, 1) as u8;
+        // Day of the month of the first self.weekday.
+        let first_day = (self.weekday as u8 + 7 - first) % 7 + 1;
+        let mut day = first_day + 7 * (self.week as u8).min(3);
+        if self.week == WeekOfMonth::Last {
+            while day + 7 <= days_in_month(year, self.month) {
+                day += 7;
+            }
+        }
+        DateTime {
+            year,
+            month: self.month,
+            day,
+            hour: self.hour,
+            minute: 0,
+            second: 0,
+        }
+    }
+}
+
+/// DstRule is a yearly DST period, start and end may be in either order
+/// so rules for both hemispheres can be expressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DstRule {
+    pub start: DstTransition,
+    pub end: DstTransition,
+    /// offset_minutes is the DST shift, usually 60.
+    pub offset_minutes: u16,
+}
+
+impl DstRule {
+    /// european_union returns the EU rule for a zone offset_hours from
+    /// UTC: the last Sunday of March to the last Sunday of October at
+    /// 01:00 UTC.
+    pub fn european_union(offset_hours: u8) -> Self {
+        DstRule {
+            start: DstTransition {
+                month: 3,
+                week: WeekOfMonth::Last,
+                weekday: Weekday::Sunday,
+                hour: 1 + offset_hours,
+            },
+            end: DstTransition {
+                month: 10,
+                week: WeekOfMonth::Last,
+                weekday: Weekday::Sunday,
+                hour: 2 + offset_hours,
+            },
+            offset_minutes: 60,
+        }
+    }
+    /// united_states returns the US rule: the second Sunday of March to
+    /// the first Sunday of November at 02:00 local time.
+    pub fn united_states() -> Self {
+        DstRule {
+            start: DstTransition {
+                month: 3,
+                week: WeekOfMonth::Second,
+                weekday: Weekday::Sunday,
+                hour: 2,
+            },
+            end: DstTransition {
+                month: 11,
+                week: WeekOfMonth::First,
+                weekday: Weekday::Sunday,
+                hour: 2,
+            },
+            offset_minutes: 60,
+        }
+    }
+    #[inline(always)]
+    fn offset_seconds(&self) -> u32 {
+        self.offset_minutes as u32 * 60
+    }
+    /// start_end returns the start and end of DST in a year in standard
+    /// time seconds, see DateTime::to_seconds.
+    fn start_end(&self, year: u16) -> (u32, u32) {
+        let start = self.start.date_time(year).to_seconds();
+        // The end hour reads in DST.
+        let end = self
+            .end
+            .date_time(year)
+            .to_seconds()
+            .saturating_sub(self.offset_seconds());
+        (start, end)
+    }
+    /// is_dst returns true when DST applies at a standard time.
+    pub fn is_dst(&self, standard: &DateTime) -> bool {
+        let (start, end) = self.start_end(standard.year);
+        let t = standard.to_seconds();
+        match start <= end {
+            true => start <= t && t < end,
+            // Southern hemisphere, DST over the new year.
+            false => t >= start || t < end,
+        }
+    }
+    /// to_local returns the local time of a standard time.
+    pub fn to_local(&self, standard: &DateTime) -> DateTime {
+        match self.is_dst(standard) {
+            true => standard.add_seconds(self.offset_seconds() as i32),
+            false => *standard,
+        }
+    }
+    /// next_transition returns the standard time of the first DST start
+    /// or end after a standard time, and true for a start.
+    pub fn next_transition(&self, standard: &DateTime) -> (DateTime, bool) {
+        let t = standard.to_seconds();
+        let mut candidates = [(0u32, true); 4];
+        for (i, year) in [standard.year, (standard.year + 1).min(RTC_MAX_YEAR)]
+            .iter()
+            .enumerate()
+        {
+            let (start, end) = self.start_end(*year);
+            candidates[2 * i] = (start, true);
+            candidates[2 * i + 1] = (end, false);
+        }
+        let next = candidates
+            .iter()
+            .filter(|(s, _)| *s > t)
+            .min_by_key(|(s, _)| *s)
+            .copied()
+            .unwrap_or((t, false));
+        (DateTime::from_seconds(next.0), next.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_time(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime::new(year, month, day, hour, minute, second).unwrap()
+    }
+
+    /// southern returns a southern hemisphere rule, the first Sunday of
+    /// October to the first Sunday of April.
+    fn southern() -> DstRule {
+        DstRule {
+            start: DstTransition {
+                month: 10,
+                week: WeekOfMonth::First,
+                weekday: Weekday::Sunday,
+                hour: 2,
+            },
+            end: DstTransition {
+                month: 4,
+                week: WeekOfMonth::First,
+                weekday: Weekday::Sunday,
+                hour: 3,
+            },
+            offset_minutes: 60,
+        }
+    }
+
+    #[test]
+    fn days_since_base_counts_leap_days() {
+        assert_eq!(days_since_base(2000, 1, 1), 0);
+        assert_eq!(days_since_base(2000, 3, 1), 60);
+        assert_eq!(days_since_base(2001, 1, 1), 366);
+        assert_eq!(days_since_base(2004, 3, 1), 1521);
+        assert_eq!(days_since_base(2099, 12, 31), 36524);
+    }
+
+    #[test]
+    fn weekday_from_base() {
+        assert_eq!(weekday(2000, 1, 1), Weekday::Saturday);
+        assert_eq!(weekday(2000, 1, 2), Weekday::Sunday);
+        assert_eq!(weekday(2000, 2, 29), Weekday::Tuesday);
+        assert_eq!(weekday(2024, 2, 29), Weekday::Thursday);
+        assert_eq!(weekday(2099, 12, 31), Weekday::Thursday);
+    }
+
+    #[test]
+    fn leap_days_are_valid_dates() {
+        assert!(DateTime::new(2000, 2, 29, 0, 0, 0).is_ok());
+        assert!(DateTime::new(2024, 2, 29, 0, 0, 0).is_ok());
+        assert_eq!(
+            DateTime::new(2023, 2, 29, 0, 0, 0),
+            Err(Error::RtcInvalidDateTime)
+        );
+        assert_eq!(
+            DateTime::new(2100, 1, 1, 0, 0, 0),
+            Err(Error::RtcInvalidDateTime)
+        );
+    }
+
+    #[test]
+    fn seconds_round_trip() {
+        assert_eq!(DateTime::from_seconds(0), date_time(2000, 1, 1, 0, 0, 0));
+        for date in [
+            date_time(2000, 2, 29, 23, 59, 59),
+            date_time(2000, 12, 31, 12, 30, 1),
+            date_time(2021, 3, 28, 2, 0, 0),
+            date_time(2024, 2, 29, 0, 0, 0),
+            date_time(2099, 12, 31, 23, 59, 59),
+        ] {
+            assert_eq!(DateTime::from_seconds(date.to_seconds()), date);
+        }
+        // Every day of a leap and a common year.
+        let mut seconds = date_time(2023, 1, 1, 6, 0, 0).to_seconds();
+        while seconds < date_time(2025, 1, 1, 0, 0, 0).to_seconds() {
+            assert_eq!(DateTime::from_seconds(seconds).to_seconds(), seconds);
+            seconds += SECONDS_PER_DAY;
+        }
+    }
+
+    #[test]
+    fn seconds_saturate() {
+        let max = date_time(2099, 12, 31, 23, 59, 59);
+        assert_eq!(DateTime::from_seconds(u32::MAX), max);
+        assert_eq!(max.add_seconds(1), max);
+        assert_eq!(
+            date_time(2000, 1, 1, 0, 0, 0).add_seconds(-1),
+            date_time(2000, 1, 1, 0, 0, 0)
+        );
+        assert_eq!(
+            date_time(2023, 12, 31, 23, 0, 0).add_seconds(3600),
+            date_time(2024, 1, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn dst_transition_last_week() {
+        let eu = DstRule::european_union(1);
+        // March 2021 has four Sundays, October 2021 five.
+        assert_eq!(eu.start.date_time(2021), date_time(2021, 3, 28, 2, 0, 0));
+        assert_eq!(eu.end.date_time(2021), date_time(2021, 10, 31, 3, 0, 0));
+        let last_thursday = DstTransition {
+            month: 2,
+            week: WeekOfMonth::Last,
+            weekday: Weekday::Thursday,
+            hour: 0,
+        };
+        assert_eq!(
+            last_thursday.date_time(2024),
+            date_time(2024, 2, 29, 0, 0, 0)
+        );
+        assert_eq!(
+            last_thursday.date_time(2023),
+            date_time(2023, 2, 23, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn european_union_rule() {
+        let eu = DstRule::european_union(1);
+        assert!(!eu.is_dst(&date_time(2021, 3, 28, 1, 59, 59)));
+        assert!(eu.is_dst(&date_time(2021, 3, 28, 2, 0, 0)));
+        // 03:00 DST is 02:00 standard.
+        assert!(eu.is_dst(&date_time(2021, 10, 31, 1, 59, 59)));
+        assert!(!eu.is_dst(&date_time(2021, 10, 31, 2, 0, 0)));
+        assert_eq!(
+            eu.to_local(&date_time(2021, 7, 1, 12, 0, 0)),
+            date_time(2021, 7, 1, 13, 0, 0)
+        );
+        assert_eq!(
+            eu.next_transition(&date_time(2021, 7, 1, 0, 0, 0)),
+            (date_time(2021, 10, 31, 2, 0, 0), false)
+        );
+        assert_eq!(
+            eu.next_transition(&date_time(2021, 12, 1, 0, 0, 0)),
+            (date_time(2022, 3, 27, 2, 0, 0), true)
+        );
+    }
+
+    #[test]
+    fn united_states_rule() {
+        let us = DstRule::united_states();
+        assert_eq!(us.start.date_time(2021), date_time(2021, 3, 14, 2, 0, 0));
+        assert_eq!(us.end.date_time(2021), date_time(2021, 11, 7, 2, 0, 0));
+        assert!(!us.is_dst(&date_time(2021, 3, 14, 1, 59, 59)));
+        assert!(us.is_dst(&date_time(2021, 3, 14, 2, 0, 0)));
+        assert!(us.is_dst(&date_time(2021, 11, 7, 0, 59, 59)));
+        assert!(!us.is_dst(&date_time(2021, 11, 7, 1, 0, 0)));
+        assert_eq!(
+            us.next_transition(&date_time(2021, 12, 1, 0, 0, 0)),
+            (date_time(2022, 3, 13, 2, 0, 0), true)
+        );
+    }
+
+    #[test]
+    fn southern_hemisphere_rule() {
+        let rule = southern();
+        assert!(rule.is_dst(&date_time(2021, 1, 15, 12, 0, 0)));
+        assert!(!rule.is_dst(&date_time(2021, 7, 1, 12, 0, 0)));
+        assert!(rule.is_dst(&date_time(2021, 12, 31, 23, 59, 59)));
+        assert!(rule.is_dst(&date_time(2021, 4, 4, 1, 59, 59)));
+        assert!(!rule.is_dst(&date_time(2021, 4, 4, 2, 0, 0)));
+        assert!(!rule.is_dst(&date_time(2021, 10, 3, 1, 59, 59)));
+        assert!(rule.is_dst(&date_time(2021, 10, 3, 2, 0, 0)));
+        assert_eq!(
+            rule.next_transition(&date_time(2021, 7, 1, 0, 0, 0)),
+            (date_time(2021, 10, 3, 2, 0, 0), true)
+        );
+        assert_eq!(
+            rule.next_transition(&date_time(2022, 1, 15, 0, 0, 0)),
+            (date_time(2022, 4, 3, 2, 0, 0), false)
+        );
+    }
+}
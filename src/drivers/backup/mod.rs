@@ -4,6 +4,10 @@
 //! Chapter 19 of the trm pp 213.
 use crate::pac::BACKUP;
 
+pub mod calendar;
+pub mod rtc;
+pub mod wco;
+
 pub struct Backup {
     backup: BACKUP,
 }
@@ -0,0 +1,352 @@
+//! rtc.rs implements the real time clock (RTC) of the backup domain,
+//! trm 19.3.
+//! The RTC counts seconds to years in BCD from the backup clock, see
+//! Backup::select_backup_clock_source, with the WCO for an accurate
+//! clock. It keeps counting on the backup supply and has two alarms,
+//! each comparing any combination of the second, minute, hour, weekday,
+//! day and month. The alarms raise SRSS_INTERRUPT_BACKUP and can wake
+//! the device from Hibernate, see rtc_enable_hibernate_wakeup.
+//! The hour is held in the 24-hour or the 12-hour format, the date and
+//! time methods use the 24-hour clock in both formats.
+//!```
+//! backup.rtc_set_date_time(&DateTime::new(2021, 6, 1, 12, 0, 0)?, HourFormat::H24)?;
+//! backup.rtc_set_alarm(AlarmId::Alarm1, &Alarm::daily(7, 30, 0))?;
+//! backup.rtc_listen(AlarmId::Alarm1);
+//! let now = backup.rtc_date_time()?;
+//!```
+
+#![deny(unsafe_code)]
+
+use crate::drivers::backup::calendar::{
+    from_12_hour, from_bcd, to_12_hour, to_bcd, BcdDateTime, DateTime, Weekday,
+};
+use crate::drivers::backup::Backup;
+use crate::drivers::cpuss::interrupt::InterruptSource;
+use crate::drivers::system::System;
+use crate::error::Error;
+
+// RTC_TIME and ALMx_TIME fields.
+const TIME_SEC_SHIFT: u32 = 0;
+const TIME_MIN_SHIFT: u32 = 8;
+const TIME_HOUR_SHIFT: u32 = 16;
+const TIME_DAY_SHIFT: u32 = 24;
+const TIME_CTRL_12HR: u32 = 1 << 22;
+// RTC_DATE and ALMx_DATE fields.
+const DATE_DATE_SHIFT: u32 = 0;
+const DATE_MON_SHIFT: u32 = 8;
+const DATE_YEAR_SHIFT: u32 = 16;
+// The alarm field enables, ALM_xxx_EN, are the top bit of each byte.
+const ALARM_FIELD_EN: u32 = 0x80;
+// ALMx_DATE.ALM_EN enables the alarm.
+const ALARM_EN: u32 = 1 << 31;
+// PM flag of the 12-hour format hour.
+const HOUR_PM: u8 = 1 << 5;
+// Hibernate wakeup is masked by PWR_HIBERNATE.MASK_HIBALARM.
+const HIBERNATE_UNLOCK: u8 = 0x3a;
+
+/// HourFormat selects the RTC hour register format.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HourFormat {
+    H24,
+    H12,
+}
+
+/// AlarmId selects one of the two alarms, the value is the INTR bit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlarmId {
+    Alarm1 = 0b01,
+    Alarm2 = 0b10,
+}
+
+/// Alarm holds the alarm fields in binary, 24-hour clock. The fields
+/// set to None are masked, e.g. an alarm with only the second set
+/// matches once a minute.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Alarm {
+    pub second: Option<u8>,
+    pub minute: Option<u8>,
+    pub hour: Option<u8>,
+    pub weekday: Option<Weekday>,
+    pub day: Option<u8>,
+    pub month: Option<u8>,
+}
+
+impl Alarm {
+    /// daily returns an alarm matching a time each day.
+    pub fn daily(hour: u8, minute: u8, second: u8) -> Self {
+        Alarm {
+            second: Some(second),
+            minute: Some(minute),
+            hour: Some(hour),
+            ..Alarm::default()
+        }
+    }
+    /// at returns an alarm matching a date and time once a year, the
+    /// RTC has no year alarm.
+    pub fn at(date_time: &DateTime) -> Self {
+        Alarm {
+            second: Some(date_time.second),
+            minute: Some(date_time.minute),
+            hour: Some(date_time.hour),
+            weekday: None,
+            day: Some(date_time.day),
+            month: Some(date_time.month),
+        }
+    }
+    /// is_valid returns true when all set fields are in range.
+    fn is_valid(&self) -> bool {
+        self.second.map_or(true, |s| s <= 59)
+            && self.minute.map_or(true, |m| m <= 59)
+            && self.hour.map_or(true, |h| h <= 23)
+            && self.day.map_or(true, |d| d >= 1 && d <= 31)
+            && self.month.map_or(true, |m| m >= 1 && m <= 12)
+    }
+}
+
+/// field returns a register byte of a BCD value with its enable bit.
+#[inline(always)]
+fn field(value: Option<u8>, shift: u32) -> u32 {
+    match value {
+        Some(bcd) => (bcd as u32 | ALARM_FIELD_EN) << shift,
+        None => 0,
+    }
+}
+
+/// byte returns a register byte.
+#[inline(always)]
+fn byte(bits: u32, shift: u32) -> u8 {
+    (bits >> shift) as u8
+}
+
+/// encode_hour returns the BCD hour register field of a 24-hour hour.
+fn encode_hour(hour: u8, format: HourFormat) -> u8 {
+    match format {
+        HourFormat::H24 => to_bcd(hour),
+        HourFormat::H12 => match to_12_hour(hour) {
+            (h, true) => to_bcd(h) | HOUR_PM,
+            (h, false) => to_bcd(h),
+        },
+    }
+}
+
+/// decode_hour returns the 24-hour hour of a BCD hour register field.
+fn decode_hour(bcd: u8, format: HourFormat) -> u8 {
+    match format {
+        HourFormat::H24 => from_bcd(bcd & 0x3f),
+        HourFormat::H12 => from_12_hour(from_bcd(bcd & 0x1f), bcd & HOUR_PM != 0),
+    }
+}
+
+impl Backup {
+    /// rtc_set_date_time writes the date and time and the hour format.
+    /// It returns an error when the RTC is busy updating.
+    #[inline(always)]
+    pub fn rtc_set_date_time(&self, date_time: &DateTime, format: HourFormat) -> Result<(), Error> {
+        self.rtc_set_date_time_bcd(&BcdDateTime::from(date_time), format)
+    }
+    /// rtc_set_date_time_bcd writes the date and time in BCD, 24-hour
+    /// clock, and the hour format.
+    /// It returns an error when the RTC is busy updating.
+    pub fn rtc_set_date_time_bcd(
+        &self,
+        bcd: &BcdDateTime,
+        format: HourFormat,
+    ) -> Result<(), Error> {
+        let hour = encode_hour(from_bcd(bcd.hour), format);
+        let ctrl_12hr = match format {
+            HourFormat::H12 => TIME_CTRL_12HR,
+            HourFormat::H24 => 0,
+        };
+        let time = (bcd.second as u32) << TIME_SEC_SHIFT
+            | (bcd.minute as u32) << TIME_MIN_SHIFT
+            | (hour as u32) << TIME_HOUR_SHIFT
+            | ctrl_12hr
+            | (bcd.weekday as u32) << TIME_DAY_SHIFT;
+        let date = (bcd.day as u32) << DATE_DATE_SHIFT
+            | (bcd.month as u32) << DATE_MON_SHIFT
+            | (bcd.year as u32) << DATE_YEAR_SHIFT;
+        self.rtc_write(|backup| {
+            backup.write_rtc_time(time);
+            backup.write_rtc_date(date);
+        })
+    }
+    /// rtc_date_time returns the date and time.
+    /// It returns an error when the RTC is busy or holds an invalid date,
+    /// e.g. after a backup domain reset before it was set.
+    #[inline(always)]
+    pub fn rtc_date_time(&self) -> Result<DateTime, Error> {
+        self.rtc_date_time_bcd()?.to_date_time()
+    }
+    /// rtc_date_time_bcd returns the date and time in BCD, 24-hour clock.
+    /// It returns an error when the RTC is busy updating.
+    pub fn rtc_date_time_bcd(&self) -> Result<BcdDateTime, Error> {
+        let (time, date) = self.rtc_read()?;
+        let hour = decode_hour(byte(time, TIME_HOUR_SHIFT), self.rtc_hour_format());
+        Ok(BcdDateTime {
+            year: byte(date, DATE_YEAR_SHIFT),
+            month: byte(date, DATE_MON_SHIFT) & 0x1f,
+            day: byte(date, DATE_DATE_SHIFT) & 0x3f,
+            weekday: byte(time, TIME_DAY_SHIFT) & 0x07,
+            hour: to_bcd(hour),
+            minute: byte(time, TIME_MIN_SHIFT) & 0x7f,
+            second: byte(time, TIME_SEC_SHIFT) & 0x7f,
+        })
+    }
+    /// rtc_hour_format returns the format of the hour registers.
+    #[inline(always)]
+    pub fn rtc_hour_format(&self) -> HourFormat {
+        match self.read_rtc_time() & TIME_CTRL_12HR {
+            0 => HourFormat::H24,
+            _ => HourFormat::H12,
+        }
+    }
+    /// rtc_set_hour_format changes the hour format, keeping the time.
+    pub fn rtc_set_hour_format(&self, format: HourFormat) -> Result<(), Error> {
+        let bcd = self.rtc_date_time_bcd()?;
+        self.rtc_set_date_time_bcd(&bcd, format)
+    }
+    /// rtc_is_busy returns true while the RTC registers are being
+    /// updated and cannot be read or written.
+    #[inline(always)]
+    pub fn rtc_is_busy(&self) -> bool {
+        self.backup.status.read().rtc_busy().bit_is_set()
+    }
+    /// rtc_set_alarm writes and enables an alarm, the hour is stored in
+    /// the current hour format. Set the alarm after changing the format.
+    /// It returns an error when a field is out of range or the RTC is
+    /// busy updating.
+    pub fn rtc_set_alarm(&self, id: AlarmId, alarm: &Alarm) -> Result<(), Error> {
+        if !alarm.is_valid() {
+            return Err(Error::RtcInvalidAlarm);
+        }
+        let format = self.rtc_hour_format();
+        let time = field(alarm.second.map(to_bcd), TIME_SEC_SHIFT)
+            | field(alarm.minute.map(to_bcd), TIME_MIN_SHIFT)
+            | field(alarm.hour.map(|h| encode_hour(h, format)), TIME_HOUR_SHIFT)
+            | field(alarm.weekday.map(|d| d as u8), TIME_DAY_SHIFT);
+        let date = field(alarm.day.map(to_bcd), DATE_DATE_SHIFT)
+            | field(alarm.month.map(to_bcd), DATE_MON_SHIFT)
+            | ALARM_EN;
+        self.rtc_write(|backup| backup.write_alarm(id, time, date))
+    }
+    /// rtc_disable_alarm disables an alarm.
+    pub fn rtc_disable_alarm(&self, id: AlarmId) -> Result<(), Error> {
+        self.rtc_write(|backup| backup.write_alarm(id, 0, 0))
+    }
+    /// rtc_listen forwards an alarm to SRSS_INTERRUPT_BACKUP.
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    pub fn rtc_listen(&self, id: AlarmId) -> () {
+        //Safety: the alarm bits are valid INTR_MASK bits.
+        self.backup
+            .intr_mask
+            .modify(|r, w| unsafe { w.bits(r.bits() | id as u32) });
+    }
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    pub fn rtc_unlisten(&self, id: AlarmId) -> () {
+        //Safety: the alarm bits are valid INTR_MASK bits.
+        self.backup
+            .intr_mask
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(id as u32)) });
+    }
+    /// rtc_alarm_pending returns true when an alarm has matched.
+    #[inline(always)]
+    pub fn rtc_alarm_pending(&self, id: AlarmId) -> bool {
+        self.backup.intr.read().bits() & id as u32 != 0
+    }
+    /// rtc_clear_alarm clears a matched alarm, INTR is write one to clear.
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    pub fn rtc_clear_alarm(&self, id: AlarmId) -> () {
+        //Safety: the alarm bits are valid INTR bits.
+        self.backup.intr.write(|w| unsafe { w.bits(id as u32) });
+    }
+    /// rtc_interrupt returns the interrupt source of the alarms.
+    #[inline(always)]
+    pub fn rtc_interrupt(&self) -> InterruptSource {
+        InterruptSource::SRSS_INTERRUPT_BACKUP
+    }
+    /// rtc_enable_hibernate_wakeup lets the alarms forwarded by
+    /// rtc_listen wake the device from Hibernate.
+    #[allow(unsafe_code)]
+    pub fn rtc_enable_hibernate_wakeup(&self, system: &System) -> () {
+        //Safety: 0x3a is the PWR_HIBERNATE unlock key.
+        system
+            .srss
+            .pwr_hibernate
+            .modify(|_, w| unsafe { w.unlock().bits(HIBERNATE_UNLOCK) });
+        system
+            .srss
+            .pwr_hibernate
+            .modify(|_, w| w.mask_hibalarm().set_bit());
+    }
+    /// rtc_disable_hibernate_wakeup stops the alarms waking the device
+    /// from Hibernate.
+    #[allow(unsafe_code)]
+    pub fn rtc_disable_hibernate_wakeup(&self, system: &System) -> () {
+        //Safety: 0x3a is the PWR_HIBERNATE unlock key.
+        system
+            .srss
+            .pwr_hibernate
+            .modify(|_, w| unsafe { w.unlock().bits(HIBERNATE_UNLOCK) });
+        system
+            .srss
+            .pwr_hibernate
+            .modify(|_, w| w.mask_hibalarm().clear_bit());
+    }
+
+    /// rtc_read copies the counters to RTC_TIME and RTC_DATE, they are
+    /// held while RTC_RW.READ is set.
+    fn rtc_read(&self) -> Result<(u32, u32), Error> {
+        if self.rtc_is_busy() {
+            return Err(Error::RtcBusy);
+        }
+        self.backup.rtc_rw.modify(|_, w| w.read().set_bit());
+        let time = self.read_rtc_time();
+        let date = self.backup.rtc_date.read().bits();
+        self.backup.rtc_rw.modify(|_, w| w.read().clear_bit());
+        Ok((time, date))
+    }
+    /// rtc_write runs f with RTC_RW.WRITE set, the written registers are
+    /// copied to the counters when it is cleared.
+    fn rtc_write<F: FnOnce(&Self) -> ()>(&self, f: F) -> Result<(), Error> {
+        if self.rtc_is_busy() {
+            return Err(Error::RtcBusy);
+        }
+        self.backup.rtc_rw.modify(|_, w| w.write().set_bit());
+        f(self);
+        self.backup.rtc_rw.modify(|_, w| w.write().clear_bit());
+        Ok(())
+    }
+    #[inline(always)]
+    fn read_rtc_time(&self) -> u32 {
+        self.backup.rtc_time.read().bits()
+    }
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    fn write_rtc_time(&self, time: u32) -> () {
+        //Safety: composed from the RTC_TIME fields.
+        self.backup.rtc_time.write(|w| unsafe { w.bits(time) });
+    }
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    fn write_rtc_date(&self, date: u32) -> () {
+        //Safety: composed from the RTC_DATE fields.
+        self.backup.rtc_date.write(|w| unsafe { w.bits(date) });
+    }
+    #[allow(unsafe_code)]
+    fn write_alarm(&self, id: AlarmId, time: u32, date: u32) -> () {
+        //Safety: composed from the ALMx_TIME and ALMx_DATE fields.
+        match id {
+            AlarmId::Alarm1 => {
+                self.backup.alm1_time.write(|w| unsafe { w.bits(time) });
+                self.backup.alm1_date.write(|w| unsafe { w.bits(date) });
+            }
+            AlarmId::Alarm2 => {
+                self.backup.alm2_time.write(|w| unsafe { w.bits(time) });
+                self.backup.alm2_date.write(|w| unsafe { w.bits(date) });
+            }
+        }
+    }
+}
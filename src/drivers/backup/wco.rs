@@ -0,0 +1,97 @@
+//! wco.rs implements the 32.768kHz watch crystal oscillator (WCO).
+//! The WCO lives in the backup domain so it keeps running while the
+//! backup supply is present. It can drive a crystal connected to the
+//! WCO_IN and WCO_OUT pins (P0.0 and P0.1) or, in bypass mode, accept
+//! an external 32.768kHz clock on WCO_OUT.
+//! trm 20.2.5 Watch Crystal Oscillator.
+
+use crate::drivers::backup::Backup;
+use crate::drivers::system::startup::{block_on, Timeout};
+use crate::error::Error;
+
+pub const WCO_FREQ: u32 = 32_768;
+
+pub enum WcoMode {
+    Crystal, // Drive a watch crystal.
+    Bypass,  // External clock on WCO_OUT, the amplifier is bypassed.
+}
+
+pub enum BackupClockSource {
+    Wco = 0,
+    AltBak = 1,
+}
+
+/// WcoStartup starts the WCO without blocking, see
+/// drivers::system::startup.
+pub struct WcoStartup {
+    started: bool,
+    timeout: Timeout,
+}
+
+impl WcoStartup {
+    pub fn new(timeout_us: u32) -> Self {
+        WcoStartup {
+            started: false,
+            timeout: Timeout::new(timeout_us),
+        }
+    }
+    pub fn poll(&mut self, backup: &Backup, elapsed_us: u32) -> nb::Result<(), Error> {
+        if !self.started {
+            backup.backup.ctl.modify(|_, w| w.wco_en().set_bit());
+            self.started = true;
+        }
+        if backup.wco_is_ok() {
+            return Ok(());
+        }
+        self.timeout
+            .wait(elapsed_us, Error::WcoStartupCouldNotBeCompletedBeforeTimeout)
+            .map_err(|e| {
+                if let nb::Error::Other(_) = e {
+                    backup.stop_wco();
+                }
+                e
+            })
+    }
+}
+
+impl Backup {
+    /// configure_wco selects crystal or bypass mode. The mode can only
+    /// be changed while the WCO is disabled so the WCO is stopped first.
+    #[inline(always)]
+    pub fn configure_wco(&self, mode: WcoMode) -> () {
+        self.stop_wco();
+        match mode {
+            WcoMode::Crystal => self.backup.ctl.modify(|_, w| w.wco_bypass().clear_bit()),
+            WcoMode::Bypass => self.backup.ctl.modify(|_, w| w.wco_bypass().set_bit()),
+        }
+    }
+    /// start_wco enables the WCO and waits until it is stable, see
+    /// WcoStartup for a non-blocking version.
+    /// timeout_us is the startup timeout in (approximately) microseconds,
+    /// watch crystals can take 500ms or more to start.
+    /// On timeout the WCO is disabled again and an error returned.
+    pub fn start_wco(&self, timeout_us: u32) -> Result<(), Error> {
+        let mut startup = WcoStartup::new(timeout_us);
+        block_on(|elapsed_us| startup.poll(self, elapsed_us))
+    }
+    /// stop_wco disables the WCO. The LFCLK and backup clock must be
+    /// switched away from the WCO first.
+    #[inline(always)]
+    pub fn stop_wco(&self) -> () {
+        self.backup.ctl.modify(|_, w| w.wco_en().clear_bit());
+    }
+    /// wco_is_ok returns true once the WCO is running and stable.
+    #[inline(always)]
+    pub fn wco_is_ok(&self) -> bool {
+        self.backup.status.read().wco_ok().bit_is_set()
+    }
+    /// select_backup_clock_source selects the clock for the backup
+    /// domain, i.e. the RTC.
+    #[inline(always)]
+    pub fn select_backup_clock_source(&self, source: BackupClockSource) -> () {
+        match source {
+            BackupClockSource::Wco => self.backup.ctl.modify(|_, w| w.clk_sel().wco()),
+            BackupClockSource::AltBak => self.backup.ctl.modify(|_, w| w.clk_sel().altbak()),
+        }
+    }
+}
@@ -7,21 +7,29 @@ impl Cpuss {
     /// wait states implemented as TRM pp 33 sect 4.2.1.1
     #[inline(always)]
     pub fn configure_wait_states(&self, hf_clock_freq: u32, sys_mode: &SystemMode) -> () {
-        let ws_slow: u8;
-        let ws_fast = 0x00;
+        self.set_wait_states(Self::wait_states(hf_clock_freq, sys_mode));
+    }
+    /// wait_states returns the ROM and SRAM slow wait states needed at a
+    /// CLK_HF0 frequency in a system mode, the fast wait states are 0.
+    pub fn wait_states(hf_clock_freq: u32, sys_mode: &SystemMode) -> u8 {
         if hf_clock_freq > 100_000_000 {
             match sys_mode {
-                SystemMode::Ulp => ws_slow = 0x01,
-                _ => ws_slow = 0x01,
+                SystemMode::Ulp => 0x01,
+                _ => 0x01,
             }
         } else if hf_clock_freq > 25_000_000 {
             match sys_mode {
-                SystemMode::Ulp => ws_slow = 0x01,
-                _ => ws_slow = 0x00,
+                SystemMode::Ulp => 0x01,
+                _ => 0x00,
             }
         } else {
-            ws_slow = 0x00;
+            0x00
         }
+    }
+    /// set_wait_states sets the ROM and SRAM slow wait states.
+    #[inline(always)]
+    pub fn set_wait_states(&self, ws_slow: u8) -> () {
+        let ws_fast = 0x00;
         self.cpu_sys
             .rom_ctl
             .modify(|_, w| unsafe { w.slow_ws().bits(ws_slow) });
@@ -38,15 +46,63 @@ impl Cpuss {
             .modify(|_, w| unsafe { w.fast_ws().bits(ws_fast) });
         //ramc1 and ramc2 mentioned in code but not in trm.
     }
-    /// configure_systick_source is a minimal implementation to enable
-    /// start up.
-    /// TODO: needs full implementation.
+    /// configure_systick_source selects the SysTick external clock and
+    /// sets the SysTick calibration from its frequency:
+    ///  - TENMS: the number of source clock cycles in 10ms, minus 1.
+    ///  - SKEW: set when source_hz is not a multiple of 100Hz so TENMS
+    ///    is not exact.
+    ///  - NOREF: cleared, the external clock is available.
+    /// The external clock is used by a core when its SYST_CSR.CLKSOURCE
+    /// selects it, i.e. SystClkSource::External. Both cores share the
+    /// source and calibration.
+    /// source_hz is the frequency of the source, e.g. Clocks::lf_clk for
+    /// SystickSource::LfClk.
+    ///```
+    /// cpuss.configure_systick_source(SystickSource::Imo, IMO_FREQ);
+    ///```
     #[allow(unsafe_code)]
     #[inline(always)]
-    pub fn configure_systick_source(&self) -> () {
-        self.cpu_sys
-            .systick_ctl
-            .modify(|_, w| unsafe { w.clock_source().bits(0x00) }); // Sets the ilo as source.
+    pub fn configure_systick_source(&self, source: SystickSource, source_hz: u32) -> () {
+        let tenms = (source_hz / 100).saturating_sub(1).min(SYSTICK_TENMS_MAX);
+        //Safety: the source is a 2 bit field and tenms is limited to 24 bits.
+        self.cpu_sys.systick_ctl.modify(|_, w| unsafe {
+            w.clock_source()
+                .bits(source as u8)
+                .tenms()
+                .bits(tenms)
+                .skew()
+                .bit(source_hz % 100 != 0)
+                .noref()
+                .clear_bit()
+        });
+    }
+    /// disable_systick_reference marks the SysTick external clock as
+    /// unavailable, SYST_CALIB.NOREF, so the cores must use the core clock.
+    #[inline(always)]
+    pub fn disable_systick_reference(&self) -> () {
+        self.cpu_sys.systick_ctl.modify(|_, w| w.noref().set_bit());
+    }
+    /// systick_source_freq returns the SysTick external clock frequency
+    /// from the calibration, None when NOREF is set. TENMS rounds it down
+    /// to 100Hz, e.g. 32_700 for the 32.768kHz WCO, so the timers take
+    /// the source frequency instead.
+    #[inline(always)]
+    pub fn systick_source_freq(&self) -> Option<u32> {
+        let systick_ctl = self.cpu_sys.systick_ctl.read();
+        match systick_ctl.noref().bit_is_set() {
+            true => None,
+            false => Some((systick_ctl.tenms().bits() + 1) * 100),
+        }
     }
-    
 }
+
+/// SystickSource is the SysTick external clock, CPUSS.SYSTICK_CTL.CLOCK_SOURCE.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SystickSource {
+    LfClk = 0,    // ILO, WCO or PILO as selected for LFCLK.
+    Imo = 1,      // 8MHz IMO.
+    Eco = 2,      // ECO, must be running.
+    ClkTimer = 3, // CLK_TIMER, must be enabled.
+}
+
+const SYSTICK_TENMS_MAX: u32 = 0x00ff_ffff; // 24 bits
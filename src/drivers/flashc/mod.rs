@@ -17,31 +17,39 @@ impl Flash {
     }
     #[inline(always)]
     pub fn configure_wait_states(&self, hf_clock_freq: u32, sys_mode: &SystemMode) -> () {
-        let ws_main: u8;
+        self.set_wait_states(Self::wait_states(hf_clock_freq, sys_mode));
+    }
+    /// wait_states returns the main flash wait states needed at a
+    /// CLK_HF0 frequency in a system mode.
+    pub fn wait_states(hf_clock_freq: u32, sys_mode: &SystemMode) -> u8 {
         match sys_mode {
             SystemMode::Ulp => {
                 if hf_clock_freq > 33_000_000 {
-                    ws_main = 0x02;
+                    0x02
                 } else if hf_clock_freq > 16_000_000 {
-                    ws_main = 0x01;
+                    0x01
                 } else {
-                    ws_main = 0x00;
+                    0x00
                 }
             }
             _ => {
                 if hf_clock_freq > 120_000_000 {
-                    ws_main = 0x04;
+                    0x04
                 } else if hf_clock_freq > 87_000_000 {
-                    ws_main = 0x03;
+                    0x03
                 } else if hf_clock_freq > 58_000_000 {
-                    ws_main = 0x02
+                    0x02
                 } else if hf_clock_freq > 29_000_000 {
-                    ws_main = 0x01;
+                    0x01
                 } else {
-                    ws_main = 0x00;
+                    0x00
                 }
             }
         }
+    }
+    /// set_wait_states sets the main flash wait states.
+    #[inline(always)]
+    pub fn set_wait_states(&self, ws_main: u8) -> () {
         self.flash
             .flash_ctl
             .modify(|_, w| unsafe { w.main_ws().bits(ws_main) });
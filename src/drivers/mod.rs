@@ -11,5 +11,7 @@ pub mod cpuss;
 pub mod flashc;
 pub mod ipc;
 pub mod nvic;
+pub mod peri;
 pub mod prot;
 pub mod system;
+pub mod tcpwm;
@@ -0,0 +1,333 @@
+//! peri/mod.rs implements the peripheral clock dividers of the PERI
+//! block. The dividers divide CLK_PERI down to the clocks used by the
+//! SCB, TCPWM, SAR and other peripherals, trm 20.6 Peripheral Clock
+//! Dividers. There are four types of divider:
+//! - 8 8-bit integer dividers -- Div8_0..Div8_7
+//! - 16 16-bit integer dividers -- Div16_0..Div16_15
+//! - 4 16.5-bit fractional dividers -- DivFrac16_0..DivFrac16_3
+//! - 1 24.5-bit fractional divider -- DivFrac24_0
+//! Each divider is an owned handle, split from the PERI peripheral, that
+//! can be connected to any number of peripheral clocks.
+//!```
+//! let mut dividers = p.PERI.split();
+//! let achieved = dividers.div16_0.set_frequency(&clocks, 1_000_000)?;
+//! dividers.div16_0.connect(PeripheralClock::Tcpwm0Clocks0);
+//! dividers.div16_0.enable();
+//!```
+
+pub mod trigger;
+
+use crate::clocks::Clocks;
+use crate::error::Error;
+use crate::pac::PERI;
+
+/// Extension trait to split the PERI peripheral into independent
+/// clock dividers.
+pub trait DividerExt {
+    /// The parts to split the PERI into.
+    type Parts;
+
+    /// Splits the PERI block into independent clock dividers.
+    fn split(self) -> Self::Parts;
+}
+
+/// DividerType is the TYPE_SEL encoding of the divider types.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DividerType {
+    Div8 = 0,
+    Div16 = 1,
+    Div16_5 = 2,
+    Div24_5 = 3,
+}
+
+impl DividerType {
+    /// max_int returns the largest integer divide value.
+    #[inline(always)]
+    fn max_int(self) -> u64 {
+        match self {
+            DividerType::Div8 => 1 << 8,
+            DividerType::Div16 | DividerType::Div16_5 => 1 << 16,
+            DividerType::Div24_5 => 1 << 24,
+        }
+    }
+    #[inline(always)]
+    fn is_fractional(self) -> bool {
+        self == DividerType::Div16_5 || self == DividerType::Div24_5
+    }
+}
+
+/// PeripheralClock is the index of a peripheral clock in CLOCK_CTL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PeripheralClock {
+    Scb0 = 0,
+    Scb1 = 1,
+    Scb2 = 2,
+    Scb3 = 3,
+    Scb4 = 4,
+    Scb5 = 5,
+    Scb6 = 6,
+    Scb7 = 7,
+    Scb8 = 8,
+    SmartIo8 = 9,
+    SmartIo9 = 10,
+    Tcpwm0Clocks0 = 11,
+    Tcpwm0Clocks1 = 12,
+    Tcpwm0Clocks2 = 13,
+    Tcpwm0Clocks3 = 14,
+    Tcpwm0Clocks4 = 15,
+    Tcpwm0Clocks5 = 16,
+    Tcpwm0Clocks6 = 17,
+    Tcpwm0Clocks7 = 18,
+    Tcpwm1Clocks0 = 19,
+    Tcpwm1Clocks1 = 20,
+    Tcpwm1Clocks2 = 21,
+    Tcpwm1Clocks3 = 22,
+    Tcpwm1Clocks4 = 23,
+    Tcpwm1Clocks5 = 24,
+    Tcpwm1Clocks6 = 25,
+    Tcpwm1Clocks7 = 26,
+    Tcpwm1Clocks8 = 27,
+    Tcpwm1Clocks9 = 28,
+    Tcpwm1Clocks10 = 29,
+    Tcpwm1Clocks11 = 30,
+    Tcpwm1Clocks12 = 31,
+    Tcpwm1Clocks13 = 32,
+    Tcpwm1Clocks14 = 33,
+    Tcpwm1Clocks15 = 34,
+    Tcpwm1Clocks16 = 35,
+    Tcpwm1Clocks17 = 36,
+    Tcpwm1Clocks18 = 37,
+    Tcpwm1Clocks19 = 38,
+    Tcpwm1Clocks20 = 39,
+    Tcpwm1Clocks21 = 40,
+    Tcpwm1Clocks22 = 41,
+    Tcpwm1Clocks23 = 42,
+    Csd = 43,
+    Lcd = 44,
+    Profile = 45,
+    CpussTraceIn = 46,
+    PassCtdac = 47,
+    PassPumpPeri = 48,
+    PassSar = 49,
+}
+
+/// PeriClockDivider is implemented by every divider handle.
+pub trait PeriClockDivider {
+    const TYPE: DividerType;
+    const NUM: u8;
+
+    /// set_frequency calculates and writes the divide value closest to
+    /// freq from CLK_PERI. Fractional dividers use 1/32 steps.
+    /// It returns the frequency achieved or an error when freq cannot
+    /// be reached with this divider width.
+    fn set_frequency(&mut self, clocks: &Clocks, freq: u32) -> Result<u32, Error>;
+    /// frequency returns the output frequency of the divider, 0 until
+    /// set_frequency has been called.
+    fn frequency(&self) -> u32;
+
+    /// set_divider writes an integer divide value 1..=2^width.
+    /// The divide value is written as is, frequency() is not updated.
+    #[inline(always)]
+    fn set_divider(&mut self, int_div: u32) -> Result<(), Error> {
+        write_divider(Self::TYPE, Self::NUM, int_div as u64 * 32)
+    }
+    /// set_fractional_divider writes a divide value of
+    /// int_div + frac_div / 32, frac_div 0..=31.
+    /// Only the 16.5-bit and 24.5-bit dividers are fractional.
+    #[inline(always)]
+    fn set_fractional_divider(&mut self, int_div: u32, frac_div: u8) -> Result<(), Error> {
+        if !Self::TYPE.is_fractional() || frac_div > 31 {
+            return Err(Error::PeriDividerOutOfRange);
+        }
+        write_divider(Self::TYPE, Self::NUM, int_div as u64 * 32 + frac_div as u64)
+    }
+    /// enable starts the divider.
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    fn enable(&mut self) -> () {
+        //Safety: DIV_CMD writes are atomic and only act on this divider.
+        //        PA_TYPE_SEL = 3 and PA_DIV_SEL = 0xff select no phase
+        //        alignment.
+        unsafe {
+            (*PERI::PTR).div_cmd.write(|w| {
+                w.div_sel()
+                    .bits(Self::NUM)
+                    .type_sel()
+                    .bits(Self::TYPE as u8)
+                    .pa_div_sel()
+                    .bits(0xff)
+                    .pa_type_sel()
+                    .bits(0x3)
+                    .enable()
+                    .set_bit()
+            });
+            while (*PERI::PTR).div_cmd.read().enable().bit_is_set() {}
+        }
+    }
+    /// disable stops the divider, connected peripherals stop clocking.
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    fn disable(&mut self) -> () {
+        //Safety: DIV_CMD writes are atomic and only act on this divider.
+        unsafe {
+            (*PERI::PTR).div_cmd.write(|w| {
+                w.div_sel()
+                    .bits(Self::NUM)
+                    .type_sel()
+                    .bits(Self::TYPE as u8)
+                    .disable()
+                    .set_bit()
+            });
+            while (*PERI::PTR).div_cmd.read().disable().bit_is_set() {}
+        }
+    }
+    /// connect selects this divider as the clock of a peripheral.
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    fn connect(&self, pclk: PeripheralClock) -> () {
+        //Safety: each CLOCK_CTL register belongs to a single peripheral.
+        unsafe {
+            (*PERI::PTR).clock_ctl[pclk as usize].write(|w| {
+                w.div_sel()
+                    .bits(Self::NUM)
+                    .type_sel()
+                    .bits(Self::TYPE as u8)
+            })
+        }
+    }
+}
+
+/// divide_value returns the divide value for freq from source_hz in
+/// 1/32 steps, rounded to an integer for integer dividers.
+pub fn divide_value(div_type: DividerType, source_hz: u32, freq: u32) -> Result<u64, Error> {
+    if freq == 0 {
+        return Err(Error::PeriDividerOutOfRange);
+    }
+    let source = source_hz as u64;
+    let freq = freq as u64;
+    let div32 = match div_type.is_fractional() {
+        true => (source * 32 + freq / 2) / freq,
+        false => ((source + freq / 2) / freq) * 32,
+    };
+    if div32 < 32 || div32 / 32 > div_type.max_int() {
+        return Err(Error::PeriDividerOutOfRange);
+    }
+    Ok(div32)
+}
+
+/// write_divider writes a divide value in 1/32 steps, the register
+/// holds the integer part minus one.
+#[allow(unsafe_code)]
+fn write_divider(div_type: DividerType, num: u8, div32: u64) -> Result<(), Error> {
+    let int_div = div32 / 32;
+    if int_div == 0 || int_div > div_type.max_int() {
+        return Err(Error::PeriDividerOutOfRange);
+    }
+    let int_bits = (int_div - 1) as u32;
+    let frac_bits = (div32 % 32) as u8;
+    let n = num as usize;
+    //Safety: the value is checked against the width of the divider and
+    //        each divider control register belongs to a single handle.
+    unsafe {
+        match div_type {
+            DividerType::Div8 => (*PERI::PTR).div_8_ctl[n]
+                .modify(|_, w| w.int8_div().bits(int_bits as u8)),
+            DividerType::Div16 => (*PERI::PTR).div_16_ctl[n]
+                .modify(|_, w| w.int16_div().bits(int_bits as u16)),
+            DividerType::Div16_5 => (*PERI::PTR).div_16_5_ctl[n].modify(|_, w| {
+                w.int16_div()
+                    .bits(int_bits as u16)
+                    .frac5_div()
+                    .bits(frac_bits)
+            }),
+            DividerType::Div24_5 => (*PERI::PTR).div_24_5_ctl[n]
+                .modify(|_, w| w.int24_div().bits(int_bits).frac5_div().bits(frac_bits)),
+        }
+    }
+    Ok(())
+}
+
+macro_rules! dividers {
+    ([
+     $($DIV:ident: ($div:ident, $TYPE:ident, $num:expr)),+
+    ]) => {
+        /// PERI clock dividers
+        pub struct Dividers {
+            $(
+                /// Divider
+                pub $div: $DIV,
+            )+
+        }
+
+        impl DividerExt for PERI {
+            type Parts = Dividers;
+
+            fn split(self) -> Dividers {
+                Dividers {
+                    $(
+                        $div: $DIV { freq: 0 },
+                    )+
+                }
+            }
+        }
+
+        $(
+            /// Divider
+            pub struct $DIV {
+                freq: u32,
+            }
+
+            impl PeriClockDivider for $DIV {
+                const TYPE: DividerType = DividerType::$TYPE;
+                const NUM: u8 = $num;
+
+                fn set_frequency(&mut self, clocks: &Clocks, freq: u32) -> Result<u32, Error> {
+                    let source = clocks.peri_clk();
+                    let div32 = divide_value(Self::TYPE, source, freq)?;
+                    write_divider(Self::TYPE, Self::NUM, div32)?;
+                    self.freq = (source as u64 * 32 / div32) as u32;
+                    Ok(self.freq)
+                }
+                #[inline(always)]
+                fn frequency(&self) -> u32 {
+                    self.freq
+                }
+            }
+        )+
+    };
+}
+
+dividers!([
+    Div8_0: (div8_0, Div8, 0),
+    Div8_1: (div8_1, Div8, 1),
+    Div8_2: (div8_2, Div8, 2),
+    Div8_3: (div8_3, Div8, 3),
+    Div8_4: (div8_4, Div8, 4),
+    Div8_5: (div8_5, Div8, 5),
+    Div8_6: (div8_6, Div8, 6),
+    Div8_7: (div8_7, Div8, 7),
+
+    Div16_0: (div16_0, Div16, 0),
+    Div16_1: (div16_1, Div16, 1),
+    Div16_2: (div16_2, Div16, 2),
+    Div16_3: (div16_3, Div16, 3),
+    Div16_4: (div16_4, Div16, 4),
+    Div16_5: (div16_5, Div16, 5),
+    Div16_6: (div16_6, Div16, 6),
+    Div16_7: (div16_7, Div16, 7),
+    Div16_8: (div16_8, Div16, 8),
+    Div16_9: (div16_9, Div16, 9),
+    Div16_10: (div16_10, Div16, 10),
+    Div16_11: (div16_11, Div16, 11),
+    Div16_12: (div16_12, Div16, 12),
+    Div16_13: (div16_13, Div16, 13),
+    Div16_14: (div16_14, Div16, 14),
+    Div16_15: (div16_15, Div16, 15),
+
+    DivFrac16_0: (div_frac16_0, Div16_5, 0),
+    DivFrac16_1: (div_frac16_1, Div16_5, 1),
+    DivFrac16_2: (div_frac16_2, Div16_5, 2),
+    DivFrac16_3: (div_frac16_3, Div16_5, 3),
+
+    DivFrac24_0: (div_frac24_0, Div24_5, 0)
+]);
@@ -0,0 +1,58 @@
+//! peri/trigger.rs implements the trigger multiplexer of the PERI block,
+//! trm 16 Trigger Multiplexer. Each trigger group selects, for each of
+//! its outputs, one of its inputs. The inputs of a group include the
+//! peri.tr_io_input lines from the pins and the outputs of the other
+//! peripherals, the outputs are e.g. the tr_in lines of a TCPWM block.
+//! The group, input and output numbers are given in the device trigger
+//! tables.
+
+#![deny(unsafe_code)]
+
+use crate::pac::PERI;
+
+/// TriggerRoute connects a trigger group input to one of its outputs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TriggerRoute {
+    pub group: u8,
+    pub input: u8,
+    pub output: u8,
+}
+
+impl TriggerRoute {
+    #[inline(always)]
+    pub fn new(group: u8, input: u8, output: u8) -> Self {
+        TriggerRoute {
+            group,
+            input,
+            output,
+        }
+    }
+}
+
+/// connect_trigger selects the input of the route on its output, the
+/// trigger passes as a level, not inverted.
+#[allow(unsafe_code)]
+pub fn connect_trigger(route: TriggerRoute) -> () {
+    //Safety: TR_SEL is 7 bits wide, each TR_OUT_CTL register selects
+    //        the input of a single output.
+    unsafe {
+        (*PERI::PTR).tr_gr[route.group as usize].tr_out_ctl[route.output as usize].write(|w| {
+            w.tr_sel()
+                .bits(route.input & 0x7f)
+                .tr_inv()
+                .clear_bit()
+                .tr_edge()
+                .clear_bit()
+        })
+    }
+}
+
+/// disconnect_trigger selects input 0, the constant 0, on the output of
+/// the route.
+#[allow(unsafe_code)]
+pub fn disconnect_trigger(route: TriggerRoute) -> () {
+    //Safety: each TR_OUT_CTL register selects the input of a single output.
+    unsafe {
+        (*PERI::PTR).tr_gr[route.group as usize].tr_out_ctl[route.output as usize].write(|w| w.bits(0))
+    }
+}
@@ -0,0 +1,207 @@
+//! clk_cal.rs implements clock measurement with the clock calibration
+//! counters, trm 20.7 Clock Calibration Counters.
+//! Counter 1 counts down a number of cycles of the measured clock while
+//! counter 2 counts up cycles of the reference clock. When counter 1
+//! reaches zero the measured frequency is:
+//! measured_hz = reference_hz * counter1 / counter2
+//! The clocks are routed to the counters through CLK_OUTPUT_FAST and
+//! CLK_OUTPUT_SLOW.
+
+#![deny(unsafe_code)]
+
+use cortex_m::asm::delay;
+
+use crate::drivers::system::System;
+use crate::error::Error;
+
+/// MeasureClock is a clock that can be routed to the calibration
+/// counters. The low byte is the CLK_OUTPUT_SLOW or PATH/HF select, the
+/// second byte the CLK_OUTPUT_FAST select.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeasureClock {
+    Ilo = 0x001,
+    Wco = 0x002,
+    AltLf = 0x004,
+    LfClk = 0x005,
+    Imo = 0x006,
+    Pilo = 0x008,
+    Eco = 0x101,
+    ExtClk = 0x102,
+    AltHf = 0x103,
+    TimerClk = 0x104,
+    Path0 = 0x500,
+    Path1 = 0x501,
+    Path2 = 0x502,
+    Path3 = 0x503,
+    Path4 = 0x504,
+    Hf0 = 0x600,
+    Hf1 = 0x601,
+    Hf2 = 0x602,
+    Hf3 = 0x603,
+    Hf4 = 0x604,
+}
+
+// CLK_OUTPUT_FAST select for the output of CLK_OUTPUT_SLOW.
+const FAST_SEL_SLOW_CLOCKS: u8 = 7;
+// Largest count of the 24 bit calibration counters.
+const CLK_CAL_COUNTER_MAX: u32 = 0x00ff_ffff;
+// ILO trim limits and nominal frequency.
+const ILO_FTRIM_MAX: u8 = 0x3f;
+pub const ILO_TARGET_FREQ: u32 = 32_000;
+// Number of ILO cycles per measurement when trimming, ~8ms.
+const ILO_MEASURE_CYCLES: u32 = 256;
+const ILO_MEASURE_TIMEOUT_US: u32 = 20_000;
+
+impl MeasureClock {
+    /// selects returns (fast_sel, path_sel, hfclk_sel, slow_sel).
+    fn selects(self) -> (u8, u8, u8, u8) {
+        let code = self as u16;
+        let group = (code >> 8) as u8;
+        let low = (code & 0xff) as u8;
+        match group {
+            0 => (FAST_SEL_SLOW_CLOCKS, 0, 0, low), // slow clocks via CLK_OUTPUT_SLOW
+            1 => (low, 0, 0, 0),                    // fast clocks selected directly
+            5 => (group, low, 0, 0),                // CLK_PATH selected by PATH_SEL
+            _ => (group, 0, low, 0),                // CLK_HF selected by HFCLK_SEL
+        }
+    }
+}
+
+impl System {
+    /// measure_clock measures the frequency of a clock against a
+    /// reference clock of known frequency.
+    ///  - measured: the clock to measure.
+    ///  - measured_cycles: the number of measured clock cycles to count,
+    ///    more cycles give a more accurate result but take longer.
+    ///  - reference: the reference clock, it should be faster than the
+    ///    measured clock for accuracy, e.g. the IMO or ECO to measure the
+    ///    ILO.
+    ///  - reference_hz: the frequency of the reference clock.
+    ///  - timeout_us: the measurement timeout in (approximately) microseconds.
+    /// It returns the measured frequency in Hz.
+    ///```
+    /// // Measure the ILO against the IMO.
+    /// let ilo_hz = system.measure_clock(MeasureClock::Ilo, 256, MeasureClock::Imo, 8_000_000, 20_000)?;
+    ///```
+    #[allow(unsafe_code)]
+    pub fn measure_clock(
+        &self,
+        measured: MeasureClock,
+        measured_cycles: u32,
+        reference: MeasureClock,
+        reference_hz: u32,
+        mut timeout_us: u32,
+    ) -> Result<u32, Error> {
+        if measured_cycles == 0 || measured_cycles > CLK_CAL_COUNTER_MAX {
+            return Err(Error::ClockMeasurementCountOutOfRange);
+        }
+        if self.srss.clk_cal_cnt1.read().cal_counter_done().bit_is_clear() {
+            return Err(Error::ClockMeasurementBusy);
+        }
+        let (fast0, path0, hf0, slow0) = measured.selects();
+        let (fast1, path1, hf1, slow1) = reference.selects();
+        //Safety: the selects come from the MeasureClock encoding.
+        self.srss.clk_output_slow.modify(|_, w| unsafe {
+            w.slow_sel0().bits(slow0).slow_sel1().bits(slow1)
+        });
+        self.srss.clk_output_fast.modify(|_, w| unsafe {
+            w.fast_sel0()
+                .bits(fast0)
+                .path_sel0()
+                .bits(path0)
+                .hfclk_sel0()
+                .bits(hf0)
+                .fast_sel1()
+                .bits(fast1)
+                .path_sel1()
+                .bits(path1)
+                .hfclk_sel1()
+                .bits(hf1)
+        });
+        // Writing counter 1 starts the measurement.
+        self.srss
+            .clk_cal_cnt1
+            .write(|w| unsafe { w.cal_counter1().bits(measured_cycles) });
+        while self.srss.clk_cal_cnt1.read().cal_counter_done().bit_is_clear() {
+            if timeout_us == 0 {
+                return Err(Error::ClockMeasurementCouldNotBeCompletedBeforeTimeout);
+            }
+            timeout_us -= 1;
+            delay(100);
+        }
+        let reference_cycles = self.srss.clk_cal_cnt2.read().cal_counter2().bits();
+        if reference_cycles == 0 {
+            return Err(Error::ClockMeasurementReferenceNotRunning);
+        }
+        Ok((reference_hz as u64 * measured_cycles as u64 / reference_cycles as u64) as u32)
+    }
+
+    /// trim_ilo adjusts CLK_TRIM_ILO_CTL.ILO_FTRIM until the ILO is
+    /// within tolerance_ppm of 32kHz, measuring it against reference.
+    /// Each trim step changes the ILO by about 1.5% so tolerances below
+    /// ~7500ppm may not be reachable, the closest trim is then kept.
+    /// It returns the measured ILO frequency which can be passed to
+    /// System::wdt_start_lfclk or ClockConfig::lfclk_freq, or an error
+    /// when the closest trim is outside tolerance_ppm.
+    pub fn trim_ilo(
+        &self,
+        reference: MeasureClock,
+        reference_hz: u32,
+        tolerance_ppm: u32,
+    ) -> Result<u32, Error> {
+        let measure = || {
+            self.measure_clock(
+                MeasureClock::Ilo,
+                ILO_MEASURE_CYCLES,
+                reference,
+                reference_hz,
+                ILO_MEASURE_TIMEOUT_US,
+            )
+        };
+        let error_ppm =
+            |freq: u32| (freq as i64 - ILO_TARGET_FREQ as i64).abs() as u64 * 1_000_000 / ILO_TARGET_FREQ as u64;
+
+        let mut trim = self.srss.clk_trim_ilo_ctl.read().ilo_ftrim().bits();
+        let mut freq = measure()?;
+        let mut best = (trim, freq);
+        // A higher trim value gives a higher frequency.
+        let step_up = freq < ILO_TARGET_FREQ;
+        while error_ppm(freq) > tolerance_ppm as u64 {
+            let next = match step_up {
+                true if trim < ILO_FTRIM_MAX => trim + 1,
+                false if trim > 0 => trim - 1,
+                _ => break,
+            };
+            self.set_ilo_trim(next);
+            trim = next;
+            freq = measure()?;
+            if error_ppm(freq) < error_ppm(best.1) {
+                best = (trim, freq);
+            }
+            // Stop once the target has been crossed.
+            if (freq < ILO_TARGET_FREQ) != step_up {
+                break;
+            }
+        }
+        if best.0 != trim {
+            self.set_ilo_trim(best.0);
+        }
+        match error_ppm(best.1) > tolerance_ppm as u64 {
+            true => Err(Error::IloTrimOutOfTolerance),
+            false => Ok(best.1),
+        }
+    }
+
+    /// set_ilo_trim writes ILO_FTRIM, the register is protected by the
+    /// watchdog lock.
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    fn set_ilo_trim(&self, trim: u8) -> () {
+        self.wdt_unlock();
+        //Safety: trim is limited to ILO_FTRIM_MAX by the caller.
+        self.srss
+            .clk_trim_ilo_ctl
+            .modify(|_, w| unsafe { w.ilo_ftrim().bits(trim) });
+        self.wdt_lock();
+    }
+}
@@ -0,0 +1,405 @@
+//! clock_tree.rs reads back the clock tree as configured by whatever
+//! startup code ran before the HAL, e.g. a C bootloader or the CM0+
+//! application.
+//! System::clock_tree reads the selects, FLL/PLL configuration and
+//! dividers into a ClockTreeRegs snapshot. ClockTreeRegs::frequencies
+//! computes the effective frequencies from the snapshot alone so it has
+//! no register access and can be run on the host.
+//! Sources with no fixed frequency (ECO, EXTCLK, ALTHF, ALTLF and the
+//! DSI outputs) must be provided in SourceFreqs, clocks derived from an
+//! unknown source are reported as None.
+
+#![deny(unsafe_code)]
+
+use crate::clocks::{ILO_FREQ, IMO_FREQ, PILO_FREQ, WCO_FREQ};
+use crate::drivers::cpuss::Cpuss;
+use crate::drivers::system::System;
+
+// CLK_PATH_SELECT.PATH_MUX values.
+const PATH_MUX_IMO: u8 = 0;
+const PATH_MUX_EXTCLK: u8 = 1;
+const PATH_MUX_ECO: u8 = 2;
+const PATH_MUX_ALTHF: u8 = 3;
+const PATH_MUX_DSI_MUX: u8 = 4;
+// CLK_DSI_SELECT.DSI_MUX values for the low frequency clocks.
+const DSI_MUX_ILO: u8 = 16;
+const DSI_MUX_WCO: u8 = 17;
+const DSI_MUX_ALTLF: u8 = 18;
+const DSI_MUX_PILO: u8 = 19;
+// CLK_SELECT.LFCLK_SEL values.
+const LFCLK_SEL_ILO: u8 = 0;
+const LFCLK_SEL_WCO: u8 = 1;
+const LFCLK_SEL_ALTLF: u8 = 2;
+const LFCLK_SEL_PILO: u8 = 3;
+// FLL and PLL BYPASS_SEL values, AUTO and LOCKED_OR_NOTHING follow the
+// lock status.
+const BYPASS_SEL_AUTO: u8 = 0;
+const BYPASS_SEL_LOCKED_OR_NOTHING: u8 = 1;
+const BYPASS_SEL_REF: u8 = 2;
+
+/// FllRegs holds the FLL fields of CLK_FLL_CONFIG..CLK_FLL_STATUS.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FllRegs {
+    pub enable: bool,
+    pub fll_mult: u32,
+    pub ref_div: u16,
+    pub output_div: bool,
+    pub bypass_sel: u8,
+    pub locked: bool,
+}
+
+/// PllRegs holds the PLL fields of CLK_PLL_CONFIG and CLK_PLL_STATUS.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PllRegs {
+    pub enable: bool,
+    pub feedback_div: u8,
+    pub reference_div: u8,
+    pub output_div: u8,
+    pub bypass_sel: u8,
+    pub locked: bool,
+}
+
+/// ClockTreeRegs is a snapshot of the clock tree registers, the fields
+/// hold the raw register field values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClockTreeRegs {
+    pub lfclk_sel: u8,
+    pub path_mux: [u8; 5],
+    pub dsi_mux: [u8; 5],
+    pub root_mux: [u8; 5],
+    pub root_div: [u8; 5],
+    pub root_enable: [bool; 5],
+    pub fll: FllRegs,
+    pub pll: PllRegs,
+    pub fast_int_div: u8,
+    pub peri_int_div: u8,
+    pub slow_int_div: u8,
+}
+
+/// SourceFreqs holds the frequencies of the clock sources. default()
+/// gives the nominal frequencies of the internal oscillators and WCO,
+/// the external sources are unknown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SourceFreqs {
+    pub imo: u32,
+    pub ilo: u32,
+    pub wco: u32,
+    pub pilo: u32,
+    pub eco: Option<u32>,
+    pub ext_clk: Option<u32>,
+    pub alt_hf: Option<u32>,
+    pub alt_lf: Option<u32>,
+}
+
+impl Default for SourceFreqs {
+    fn default() -> Self {
+        SourceFreqs {
+            imo: IMO_FREQ,
+            ilo: ILO_FREQ,
+            wco: WCO_FREQ,
+            pilo: PILO_FREQ,
+            eco: None,
+            ext_clk: None,
+            alt_hf: None,
+            alt_lf: None,
+        }
+    }
+}
+
+/// ClockTree holds the effective frequencies in Hz of the clock tree,
+/// None when the clock is disabled or its source frequency is unknown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockTree {
+    pub lf: Option<u32>,
+    pub paths: [Option<u32>; 5],
+    pub hf: [Option<u32>; 5],
+    pub fast: Option<u32>,
+    pub peri: Option<u32>,
+    pub slow: Option<u32>,
+}
+
+impl ClockTreeRegs {
+    /// frequencies computes the effective frequency of every path,
+    /// CLK_HF root, CLK_FAST, CLK_PERI and CLK_SLOW from the snapshot.
+    ///```
+    /// let tree = system.clock_tree(&cpuss).frequencies(&SourceFreqs::default());
+    ///```
+    pub fn frequencies(&self, sources: &SourceFreqs) -> ClockTree {
+        let lf = match self.lfclk_sel {
+            LFCLK_SEL_ILO => Some(sources.ilo),
+            LFCLK_SEL_WCO => Some(sources.wco),
+            LFCLK_SEL_ALTLF => sources.alt_lf,
+            LFCLK_SEL_PILO => Some(sources.pilo),
+            _ => None,
+        };
+        let mut paths = [None; 5];
+        for (i, path) in paths.iter_mut().enumerate() {
+            let source = self.path_source_freq(i, sources);
+            *path = match i {
+                0 => self.fll_freq(source),
+                1 => self.pll_freq(source),
+                _ => source,
+            };
+        }
+        let mut hf = [None; 5];
+        for (i, root) in hf.iter_mut().enumerate() {
+            // CLK_HF[0] is always enabled.
+            if i != 0 && !self.root_enable[i] {
+                continue;
+            }
+            *root = paths
+                .get(self.root_mux[i] as usize)
+                .copied()
+                .flatten()
+                .map(|freq| freq >> self.root_div[i]);
+        }
+        let fast = hf[0].map(|freq| freq / (self.fast_int_div as u32 + 1));
+        let peri = hf[0].map(|freq| freq / (self.peri_int_div as u32 + 1));
+        let slow = peri.map(|freq| freq / (self.slow_int_div as u32 + 1));
+        ClockTree {
+            lf,
+            paths,
+            hf,
+            fast,
+            peri,
+            slow,
+        }
+    }
+
+    /// path_source_freq returns the frequency at the input of path i.
+    fn path_source_freq(&self, i: usize, sources: &SourceFreqs) -> Option<u32> {
+        match self.path_mux[i] {
+            PATH_MUX_IMO => Some(sources.imo),
+            PATH_MUX_EXTCLK => sources.ext_clk,
+            PATH_MUX_ECO => sources.eco,
+            PATH_MUX_ALTHF => sources.alt_hf,
+            PATH_MUX_DSI_MUX => match self.dsi_mux[i] {
+                DSI_MUX_ILO => Some(sources.ilo),
+                DSI_MUX_WCO => Some(sources.wco),
+                DSI_MUX_ALTLF => sources.alt_lf,
+                DSI_MUX_PILO => Some(sources.pilo),
+                _ => None, // DSI outputs are routed from the UDBs.
+            },
+            _ => None,
+        }
+    }
+
+    /// fll_freq returns the CLK_PATH0 frequency, the FLL output or its
+    /// reference depending on BYPASS_SEL and the lock status.
+    fn fll_freq(&self, reference: Option<u32>) -> Option<u32> {
+        let fll = &self.fll;
+        let use_output = match fll.bypass_sel {
+            BYPASS_SEL_AUTO | BYPASS_SEL_LOCKED_OR_NOTHING => fll.enable && fll.locked,
+            BYPASS_SEL_REF => false,
+            _ => true,
+        };
+        if !use_output {
+            return match fll.bypass_sel {
+                BYPASS_SEL_LOCKED_OR_NOTHING => None,
+                _ => reference,
+            };
+        }
+        if !fll.enable || fll.ref_div == 0 {
+            return None;
+        }
+        reference.map(|freq| {
+            let cco = freq as u64 * fll.fll_mult as u64 / fll.ref_div as u64;
+            (cco >> fll.output_div as u32) as u32
+        })
+    }
+
+    /// pll_freq returns the CLK_PATH1 frequency, the PLL output or its
+    /// reference depending on BYPASS_SEL and the lock status.
+    fn pll_freq(&self, reference: Option<u32>) -> Option<u32> {
+        let pll = &self.pll;
+        let use_output = match pll.bypass_sel {
+            BYPASS_SEL_AUTO | BYPASS_SEL_LOCKED_OR_NOTHING => pll.enable && pll.locked,
+            BYPASS_SEL_REF => false,
+            _ => true,
+        };
+        if !use_output {
+            return match pll.bypass_sel {
+                BYPASS_SEL_LOCKED_OR_NOTHING => None,
+                _ => reference,
+            };
+        }
+        if !pll.enable || pll.reference_div == 0 || pll.output_div == 0 {
+            return None;
+        }
+        reference.map(|freq| {
+            (freq as u64 * pll.feedback_div as u64
+                / (pll.reference_div as u64 * pll.output_div as u64)) as u32
+        })
+    }
+}
+
+impl System {
+    /// clock_tree reads the clock tree registers into a snapshot, the
+    /// CPU dividers are read from the CPUSS.
+    pub fn clock_tree(&self, cpuss: &Cpuss) -> ClockTreeRegs {
+        let mut regs = ClockTreeRegs::default();
+        regs.lfclk_sel = self.srss.clk_select.read().lfclk_sel().bits();
+        for i in 0..5 {
+            regs.path_mux[i] = self.srss.clk_path_select[i].read().path_mux().bits();
+            regs.dsi_mux[i] = self.srss.clk_dsi_select[i].read().dsi_mux().bits();
+            let root = self.srss.clk_root_select[i].read();
+            regs.root_mux[i] = root.root_mux().bits();
+            regs.root_div[i] = root.root_div().bits();
+            regs.root_enable[i] = root.enable().bit_is_set();
+        }
+
+        let fll_config = self.srss.clk_fll_config.read();
+        regs.fll = FllRegs {
+            enable: fll_config.fll_enable().bit_is_set(),
+            fll_mult: fll_config.fll_mult().bits(),
+            ref_div: self.srss.clk_fll_config2.read().fll_ref_div().bits(),
+            output_div: fll_config.fll_output_div().bit_is_set(),
+            bypass_sel: self.srss.clk_fll_config3.read().bypass_sel().bits(),
+            locked: self.srss.clk_fll_status.read().locked().bit_is_set(),
+        };
+        let pll_config = self.srss.clk_pll_config[0].read();
+        regs.pll = PllRegs {
+            enable: pll_config.enable().bit_is_set(),
+            feedback_div: pll_config.feedback_div().bits(),
+            reference_div: pll_config.reference_div().bits(),
+            output_div: pll_config.output_div().bits(),
+            bypass_sel: pll_config.bypass_sel().bits(),
+            locked: self.srss.clk_pll_status[0].read().locked().bit_is_set(),
+        };
+
+        regs.fast_int_div = cpuss.cpu_sys.cm4_clock_ctl.read().fast_int_div().bits();
+        let cm0_clock_ctl = cpuss.cpu_sys.cm0_clock_ctl.read();
+        regs.peri_int_div = cm0_clock_ctl.peri_int_div().bits();
+        regs.slow_int_div = cm0_clock_ctl.slow_int_div().bits();
+        regs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BYPASS_SEL_OUTPUT: u8 = 3;
+
+    /// fll_100mhz returns the default tree, the FLL on the IMO at 100MHz
+    /// driving CLK_HF0.
+    fn fll_100mhz() -> ClockTreeRegs {
+        ClockTreeRegs {
+            fll: FllRegs {
+                enable: true,
+                fll_mult: 500,
+                ref_div: 20,
+                output_div: true,
+                bypass_sel: BYPASS_SEL_AUTO,
+                locked: true,
+            },
+            ..ClockTreeRegs::default()
+        }
+    }
+
+    /// pll_150mhz returns a tree with the PLL on the IMO at 150MHz
+    /// driving CLK_HF0.
+    fn pll_150mhz(bypass_sel: u8, locked: bool) -> ClockTreeRegs {
+        let mut regs = ClockTreeRegs::default();
+        regs.root_mux[0] = 1;
+        regs.pll = PllRegs {
+            enable: true,
+            feedback_div: 75,
+            reference_div: 2,
+            output_div: 2,
+            bypass_sel,
+            locked,
+        };
+        regs
+    }
+
+    #[test]
+    fn fll_bypass_sel() {
+        let sources = SourceFreqs::default();
+        let mut regs = fll_100mhz();
+        assert_eq!(regs.frequencies(&sources).paths[0], Some(100_000_000));
+        // AUTO follows the lock, unlocked gives the reference.
+        regs.fll.locked = false;
+        assert_eq!(regs.frequencies(&sources).paths[0], Some(IMO_FREQ));
+        // LOCKED_OR_NOTHING gives no clock until locked.
+        regs.fll.bypass_sel = BYPASS_SEL_LOCKED_OR_NOTHING;
+        assert_eq!(regs.frequencies(&sources).paths[0], None);
+        regs.fll.locked = true;
+        assert_eq!(regs.frequencies(&sources).paths[0], Some(100_000_000));
+        // REF always gives the reference.
+        regs.fll.bypass_sel = BYPASS_SEL_REF;
+        assert_eq!(regs.frequencies(&sources).paths[0], Some(IMO_FREQ));
+        // OUTPUT ignores the lock.
+        regs.fll.bypass_sel = BYPASS_SEL_OUTPUT;
+        regs.fll.locked = false;
+        assert_eq!(regs.frequencies(&sources).paths[0], Some(100_000_000));
+    }
+
+    #[test]
+    fn pll_bypass_sel() {
+        let sources = SourceFreqs::default();
+        let tree = |bypass_sel, locked| pll_150mhz(bypass_sel, locked).frequencies(&sources);
+        assert_eq!(tree(BYPASS_SEL_AUTO, true).paths[1], Some(150_000_000));
+        assert_eq!(tree(BYPASS_SEL_AUTO, false).paths[1], Some(IMO_FREQ));
+        assert_eq!(
+            tree(BYPASS_SEL_LOCKED_OR_NOTHING, true).paths[1],
+            Some(150_000_000)
+        );
+        assert_eq!(tree(BYPASS_SEL_LOCKED_OR_NOTHING, false).paths[1], None);
+        assert_eq!(tree(BYPASS_SEL_REF, true).paths[1], Some(IMO_FREQ));
+        assert_eq!(tree(BYPASS_SEL_OUTPUT, false).paths[1], Some(150_000_000));
+        assert_eq!(tree(BYPASS_SEL_AUTO, true).hf[0], Some(150_000_000));
+    }
+
+    #[test]
+    fn root_div_and_cpu_dividers() {
+        let mut regs = fll_100mhz();
+        regs.root_div[0] = 1;
+        regs.fast_int_div = 0;
+        regs.peri_int_div = 1;
+        regs.slow_int_div = 4;
+        let tree = regs.frequencies(&SourceFreqs::default());
+        assert_eq!(tree.hf[0], Some(50_000_000));
+        assert_eq!(tree.fast, Some(50_000_000));
+        assert_eq!(tree.peri, Some(25_000_000));
+        assert_eq!(tree.slow, Some(5_000_000));
+    }
+
+    #[test]
+    fn disabled_hf_root() {
+        let mut regs = fll_100mhz();
+        regs.root_mux[1] = 0;
+        regs.root_enable[1] = false;
+        assert_eq!(regs.frequencies(&SourceFreqs::default()).hf[1], None);
+        regs.root_enable[1] = true;
+        regs.root_div[1] = 2;
+        assert_eq!(
+            regs.frequencies(&SourceFreqs::default()).hf[1],
+            Some(25_000_000)
+        );
+    }
+
+    #[test]
+    fn unknown_sources() {
+        let mut regs = fll_100mhz();
+        regs.path_mux[0] = PATH_MUX_ECO;
+        regs.path_mux[2] = PATH_MUX_EXTCLK;
+        regs.root_mux[2] = 2;
+        regs.root_enable[2] = true;
+        let tree = regs.frequencies(&SourceFreqs::default());
+        assert_eq!(tree.paths[0], None);
+        assert_eq!(tree.hf[0], None);
+        assert_eq!(tree.peri, None);
+        assert_eq!(tree.paths[2], None);
+        assert_eq!(tree.hf[2], None);
+
+        let sources = SourceFreqs {
+            eco: Some(16_000_000),
+            ext_clk: Some(24_000_000),
+            ..SourceFreqs::default()
+        };
+        let tree = regs.frequencies(&sources);
+        assert_eq!(tree.paths[0], Some(200_000_000));
+        assert_eq!(tree.hf[2], Some(24_000_000));
+    }
+}
@@ -40,10 +40,13 @@
 
 use cortex_m::asm::delay;
 
+use crate::drivers::backup::{wco::BackupClockSource, Backup};
+use crate::drivers::system::startup::{block_on, FllStartup};
 use crate::drivers::system::System;
 use core::result::Result;
 use crate::error::Error;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Clocks {
     Imo,    // 8-MHz internal main oscillator
     Ilo,    // 32-kHz internal low-speed oscillator
@@ -55,6 +58,7 @@ pub enum Clocks {
     Pll,    // Phase locked loop 10.625-150Mhz
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RootClocks {
     CpuPeriAhb = 0, // Root clock for the CPUs, PERI, and AHB
     PdmPcmI2s = 1,  // Root clock for the PDM/PCM and I2S audio subsystem
@@ -63,6 +67,7 @@ pub enum RootClocks {
     ClkOut = 4,     // Root clock for the clk_ext_pin output.
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Divider {
     NoDiv,
     Div2,
@@ -70,6 +75,7 @@ pub enum Divider {
     Div8,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PathSource {
     Imo,
     ExtClk,
@@ -77,6 +83,7 @@ pub enum PathSource {
     AltHf,
     DsiMux,
 }
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ClockPath {
     Path0 = 0,
     Path1 = 1,
@@ -102,6 +109,7 @@ pub enum DsiMux {
 }
 
 /// FLL configuration enums
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct FllConfig {
     fll_mult: u32,
     ref_div: u16,
@@ -128,11 +136,190 @@ impl FllConfig {
             cco_freq: 355,
         }
     }
+    /// for_target calculates an FLL configuration that multiplies the
+    /// reference_hz path clock up to target_hz.
+    /// tolerance is the accuracy of the reference clock in ppm, the IMO
+    /// is specified at +/-1% i.e. 10_000ppm. It is used along with the
+    /// CCO accuracy (0.25%) to set the lock tolerance.
+    /// The calculation follows trm 20.4.2.1 Configuring the FLL:
+    /// 1. Output divider is always enabled so the CCO runs at 2 * target.
+    /// 2. The CCO range is selected from the CCO frequency.
+    /// 3. ref_div = ceil(250 * reference / target), fixed at 19 for the WCO.
+    /// 4. fll_mult = ceil(cco * ref_div / reference).
+    /// 5. lock_tolerance = ceil(1.5 * fll_mult * ((1 + 0.0025)/(1 - tolerance) - 1)).
+    /// 6. igain and pgain from ki_p = 0.85 / (kcco * ref_div / reference).
+    /// 7. settling_count for a 1us settling time, fixed at 200 for the WCO.
+    /// 8. cco_freq = ln(cco / fmargin) / ln(1 + trim_step).
+    /// It returns an error when the target is outside the 24-100MHz FLL
+    /// output range or the register fields cannot hold the result.
+    ///```
+    /// // 8MHz IMO to 100MHz gives the same values as FllConfig::default()
+    /// let fll_config = FllConfig::for_target(8_000_000, 100_000_000, 10_000)?;
+    ///```
+    pub fn for_target(reference_hz: u32, target_hz: u32, tolerance: u32) -> Result<Self, Error> {
+        // Trim step per CCO range scaled by 10^8 and the minimum
+        // frequency of each CCO range in Hz.
+        const TRIM_STEPS: [u64; 5] = [110_340, 110_200, 110_000, 110_000, 117_062];
+        const F_MARGIN: [u64; 5] = [43_600_000, 58_100_000, 77_200_000, 103_000_000, 132_000_000];
+        // FllGain values scaled by 1024 and indexed by the FllGain discriminant.
+        const GAINS: [u64; 12] = [4, 8, 16, 32, 64, 128, 256, 512, 1024, 2048, 4096, 8192];
+
+        if target_hz < FLL_MIN_OUTPUT_FREQ || target_hz > FLL_MAX_OUTPUT_FREQ {
+            return Err(Error::FllTargetFrequencyOutOfRange);
+        }
+        if reference_hz == 0 || tolerance >= 1_000_000 {
+            return Err(Error::FllCouldNotBeConfigured);
+        }
+        let reference = reference_hz as u64;
+        let wco_source = reference_hz < 1_000_000;
+
+        let output_div_enable = FllOutputDiv::Enable;
+        let cco = 2 * target_hz as u64;
+        let cco_range = if cco >= 150_339_200 {
+            FllCco::Range4
+        } else if cco >= 113_009_380 {
+            FllCco::Range3
+        } else if cco >= 84_948_700 {
+            FllCco::Range2
+        } else if cco >= 63_855_600 {
+            FllCco::Range1
+        } else {
+            FllCco::Range0
+        };
+        let range = cco_range as usize;
+
+        let ref_div = match wco_source {
+            true => 19,
+            false => div_ceil(250 * reference, target_hz as u64),
+        };
+        let fll_mult = div_ceil(cco * ref_div, reference);
+
+        let accuracy = 1_002_500 * 1_000_000 / (1_000_000 - tolerance as u64) - 1_000_000;
+        let lock_tolerance = div_ceil(3 * fll_mult * accuracy, 2 * 1_000_000);
+
+        // ki_p = 0.85 / (kcco * ref_div / reference) scaled by 1024 to
+        // match GAINS, kcco being trim_step * fmargin.
+        let kcco = TRIM_STEPS[range] * F_MARGIN[range] / 1000;
+        let ki_p = (850 * 1024 * 100 * reference + kcco * ref_div / 2) / (kcco * ref_div);
+        let mut igain = GAINS.len() - 1;
+        while igain > 0 && GAINS[igain] > ki_p {
+            igain -= 1;
+        }
+        let mut pgain = GAINS.len() - 1;
+        while pgain > 0 && GAINS[pgain] > ki_p.saturating_sub(GAINS[igain]) {
+            pgain -= 1;
+        }
+
+        let settling_count = match wco_source {
+            true => 200,
+            false => {
+                let fref = (6000 * (reference / 1000) + ref_div / 2) / ref_div;
+                let divval = div_ceil(reference, 1_000_000);
+                let altval = div_ceil(divval * fref, 6_000_000) + 1;
+                if (target_hz as u64) < fref || divval > altval {
+                    divval
+                } else {
+                    altval
+                }
+            }
+        };
+
+        // cco_freq is the number of trim steps above fmargin, working in
+        // centi-Hz so the repeated multiplication keeps its precision.
+        let mut cco_freq: u64 = 0;
+        let mut freq = F_MARGIN[range] * 100;
+        loop {
+            let next = freq * (100_000_000 + TRIM_STEPS[range]) / 100_000_000;
+            if next > cco * 100 || cco_freq == FLL_CCO_FREQ_MAX {
+                break;
+            }
+            freq = next;
+            cco_freq += 1;
+        }
+
+        if ref_div > FLL_REF_DIV_MAX
+            || fll_mult > FLL_MULT_MAX
+            || lock_tolerance > FLL_LOCK_TOL_MAX
+            || settling_count > FLL_SETTLING_COUNT_MAX
+        {
+            return Err(Error::FllCouldNotBeConfigured);
+        }
+
+        Ok(Self {
+            fll_mult: fll_mult as u32,
+            ref_div: ref_div as u16,
+            cco_range,
+            output_div_enable,
+            lock_tolerance: lock_tolerance as u16,
+            igain: FllGain::from_index(igain),
+            pgain: FllGain::from_index(pgain),
+            settling_count: settling_count as u16,
+            cco_freq: cco_freq as u16,
+        })
+    }
+    /// output_freq returns the FLL output frequency produced by this
+    /// configuration from a reference_hz path clock.
+    pub fn output_freq(&self, reference_hz: u32) -> u32 {
+        let cco = reference_hz as u64 * self.fll_mult as u64 / self.ref_div as u64;
+        match self.output_div_enable {
+            FllOutputDiv::Enable => (cco / 2) as u32,
+            FllOutputDiv::Disable => cco as u32,
+        }
+    }
+    #[inline(always)]
+    pub fn fll_mult(&self) -> u32 {
+        self.fll_mult
+    }
+    #[inline(always)]
+    pub fn ref_div(&self) -> u16 {
+        self.ref_div
+    }
+    #[inline(always)]
+    pub fn cco_range(&self) -> FllCco {
+        self.cco_range
+    }
+    #[inline(always)]
+    pub fn lock_tolerance(&self) -> u16 {
+        self.lock_tolerance
+    }
+    #[inline(always)]
+    pub fn igain(&self) -> FllGain {
+        self.igain
+    }
+    #[inline(always)]
+    pub fn pgain(&self) -> FllGain {
+        self.pgain
+    }
+    #[inline(always)]
+    pub fn settling_count(&self) -> u16 {
+        self.settling_count
+    }
+    #[inline(always)]
+    pub fn cco_freq(&self) -> u16 {
+        self.cco_freq
+    }
+}
+
+/// FLL output range and register field limits, trm 20.4.2.
+pub const FLL_MIN_OUTPUT_FREQ: u32 = 24_000_000;
+pub const FLL_MAX_OUTPUT_FREQ: u32 = 100_000_000;
+const FLL_MULT_MAX: u64 = 0x3_ffff; // 18 bits
+const FLL_REF_DIV_MAX: u64 = 0x1fff; // 13 bits
+const FLL_LOCK_TOL_MAX: u64 = 0x1ff; // 9 bits
+const FLL_SETTLING_COUNT_MAX: u64 = 0x1fff; // 13 bits
+const FLL_CCO_FREQ_MAX: u64 = 0x1ff; // 9 bits
+
+#[inline(always)]
+fn div_ceil(num: u64, den: u64) -> u64 {
+    (num + den - 1) / den
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FllOutputDiv {
     Disable,
     Enable,
 }
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FllGain {
     Div256 = 0,
     Div128 = 1,
@@ -147,6 +334,24 @@ pub enum FllGain {
     Mult4 = 10,
     Mult8 = 11,
 }
+impl FllGain {
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => FllGain::Div256,
+            1 => FllGain::Div128,
+            2 => FllGain::Div64,
+            3 => FllGain::Div32,
+            4 => FllGain::Div16,
+            5 => FllGain::Div8,
+            6 => FllGain::Div4,
+            7 => FllGain::Div2,
+            8 => FllGain::Div1,
+            9 => FllGain::Mult2,
+            10 => FllGain::Mult4,
+            _ => FllGain::Mult8,
+        }
+    }
+}
 /// ClocksFllCco represents the  target frequency range
 /// of the Fll configuration.
 /// Range0--target frequency range 48-64Mhz  
@@ -154,6 +359,7 @@ pub enum FllGain {
 /// Range2--target frequency range 85-113Mhz
 /// Range3--target frequency range 113-150Mhz
 /// Range4--target frequency range 150-200Mhz
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FllCco {
     Range0,
     Range1,
@@ -220,7 +426,9 @@ impl System {
     ///  - Ilo -- relatively low accuracy and low power.
     ///  - Wco -- high accuracy clock source primary clock for RTC.
     ///  - Pilo -- works in deep sleep and active modes, higher accuracy clock e.g ECO.
-    /// It returns an error if a non-specified clock source is passed in.
+    ///            It must be started first with enable_pilo.
+    /// It returns an error if a non-specified clock source is passed in,
+    /// or the PILO is selected while disabled.
     /// Note the watchdog must be unlocked for configuration changes to
     /// clk_select to set.
     ///
@@ -239,6 +447,10 @@ impl System {
                 Ok(())
             }
             Clocks::Pilo => {
+                if !self.pilo_is_enabled() {
+                    self.wdt_lock();
+                    return Err(Error::PiloNotEnabled);
+                }
                 self.srss.clk_select.modify(|_, w| w.lfclk_sel().pilo());
                 self.wdt_lock();
                 Ok(())
@@ -269,6 +481,15 @@ impl System {
         self.wdt_lock();
     }
 
+    /// start_wco_lfclk starts the WCO then selects it as the clock for
+    /// the LFCLK and the backup domain.
+    /// On a WCO startup timeout the LFCLK and backup clocks are left
+    /// unchanged and the error is returned.
+    pub fn start_wco_lfclk(&self, backup: &Backup, timeout_us: u32) -> Result<(), Error> {
+        backup.start_wco(timeout_us)?;
+        backup.select_backup_clock_source(BackupClockSource::Wco);
+        self.configure_lfclk_source(Clocks::Wco)
+    }
     /// clocks_init_path_source configures a source clock for the path.
     /// Input paramters:
     ///  - path_num: a ClocksSelectChannelNumber member.
@@ -367,6 +588,13 @@ impl System {
     pub fn start_root_clk(&self, hf_index: RootClocks) -> () {
         self.srss.clk_root_select[hf_index as usize].modify(|_, w| w.enable().set_bit());
     }
+    /// stop_root_clk disables a CLK_HF root, CLK_HF0 cannot be disabled.
+    #[inline(always)]
+    pub fn stop_root_clk(&self, hf_index: RootClocks) -> () {
+        if hf_index != RootClocks::CpuPeriAhb {
+            self.srss.clk_root_select[hf_index as usize].modify(|_, w| w.enable().clear_bit());
+        }
+    }
 
     #[allow(unsafe_code)]
     pub fn configure_fll(&self, fll_config: FllConfig) -> () {
@@ -433,75 +661,12 @@ impl System {
             .modify(|_, w| unsafe { w.cco_freq().bits(cco_freq) });
     }
 
-    pub fn start_fll(&self, mut timeout_us: u32) -> Result<(), Error> {
-        if self.srss.clk_fll_config.read().fll_enable().bit_is_clear() {
-            let mut error = match timeout_us {
-                0 => Error::FllStartupCouldNotBeCompletedBeforeTimeout,
-                _ => Error::NoError,
-            };
-            //Config done need to start up the fll.
-            self.srss
-                .clk_trim_cco_ctl
-                .modify(|_, w| w.enable_cnt().set_bit()); //start the cco stabilisation counter.
-                                                          //To enable the FLL, first enable the CCO by writing CLK_FLL_CONFIG4.CCO_ENABLE=1 and
-            self.srss
-                .clk_fll_config4
-                .modify(|_, w| w.cco_enable().set_bit());
-            //wait until CLK_FLL_STATUS.CCO_READY==1
-            while !self.srss.clk_fll_status.read().cco_ready().bit_is_set()
-                && error == Error::NoError
-            {
-                match timeout_us > 0 {
-                    true => {
-                        timeout_us -= 1;
-                        delay(100);
-                    }
-                    false => error = Error::FllStartupFailedCcoNotReady,
-                }
-            }
-            //Next, ensure the reference clock has stabilized and CLK_FLL_CONFIG3.BYPASS_SEL=FLL_REF.
-            self.srss
-                .clk_fll_config3
-                .modify(|_, w| w.bypass_sel().fll_ref());
-            self.srss.clk_fll_config3.read().bypass_sel().is_fll_ref(); // read ensures write has completed.
-                                                                        //  Next, write FLL_ENABLE=1 and wait until
-            if timeout_us > 0 {
-                self.srss
-                    .clk_fll_config
-                    .modify(|_, w| w.fll_enable().set_bit());
-            }
-            // CLK_FLL_STATUS.LOCKED==1.
-            //wait for the system to lock
-
-            while self.srss.clk_fll_status.read().locked().bit_is_clear() && error == Error::NoError
-            {
-                match timeout_us > 0 {
-                    true => {
-                        timeout_us -= 1;
-                        delay(100);
-                    }
-                    false => error = Error::FllStartupFailedFllCouldNotBeLocked,
-                }
-            }
-
-            // Finally, write CLK_FLL_CONFIG3.BYPASS_SEL=FLL_OUT
-            // to switch to the FLL output. It takes seven reference clock cycles plus four FLL output cycles to
-            // switch to the FLL output. Do not disable the FLL before this time completes.
-
-            self.srss
-                .clk_fll_config3
-                .modify(|_, w| w.bypass_sel().fll_out());
-
-            cortex_m::asm::delay(100);
-            if timeout_us <= 0 {
-                Err(error)
-            } else {
-                Ok(())
-            }
-        } else {
-            //Fll already started
-            Ok(())
-        }
+    /// start_fll starts the configured FLL and waits for it to lock,
+    /// see FllStartup for the sequence and a non-blocking version.
+    /// timeout_us is the startup timeout in (approximately) microseconds.
+    pub fn start_fll(&self, timeout_us: u32) -> Result<(), Error> {
+        let mut startup = FllStartup::new(timeout_us);
+        block_on(|elapsed_us| startup.poll(self, elapsed_us))
     }
     #[inline(always)]
     pub fn stop_fll(&self) -> () {
@@ -573,3 +738,48 @@ impl System {
         self.wdt_lock();
     }
 } // impl System
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fll_imo_to_100mhz_matches_default() {
+        let config = FllConfig::for_target(8_000_000, 100_000_000, 10_000).unwrap();
+        assert_eq!(config, FllConfig::default());
+        assert_eq!(config.output_freq(8_000_000), 100_000_000);
+    }
+
+    #[test]
+    fn fll_wco_to_48mhz() {
+        let config = FllConfig::for_target(32_768, 48_000_000, 100).unwrap();
+        assert_eq!(config.cco_range(), FllCco::Range2);
+        assert_eq!(config.ref_div(), 19);
+        assert_eq!(config.fll_mult(), 55_665);
+        assert_eq!(config.lock_tolerance(), 218);
+        assert_eq!(config.igain(), FllGain::Div64);
+        assert_eq!(config.pgain(), FllGain::Div256);
+        assert_eq!(config.settling_count(), 200);
+        assert_eq!(config.cco_freq(), 198);
+    }
+
+    #[test]
+    fn fll_settling_count_from_external_reference() {
+        // 50MHz reference: divval 50 exceeds altval 6.
+        let config = FllConfig::for_target(50_000_000, 24_000_000, 10_000).unwrap();
+        assert_eq!(config.ref_div(), 521);
+        assert_eq!(config.settling_count(), 50);
+    }
+
+    #[test]
+    fn fll_target_out_of_range() {
+        assert_eq!(
+            FllConfig::for_target(8_000_000, 120_000_000, 10_000),
+            Err(Error::FllTargetFrequencyOutOfRange)
+        );
+        assert_eq!(
+            FllConfig::for_target(8_000_000, 20_000_000, 10_000),
+            Err(Error::FllTargetFrequencyOutOfRange)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! csv.rs implements the clock supervisors (CSV).
+//! A clock supervisor counts cycles of the monitored clock against a
+//! reference clock and acts when the monitored clock is lost.
+//! - WCO: the watch crystal is supervised against the ILO, a lost WCO
+//!   is reported in RES_CAUSE as ResetCause::LostWatchCrystalClock.
+//! - CLK_HF roots: each root is supervised against the reference clock
+//!   for loss and, optionally, for its frequency staying within a
+//!   window. A reset is reported in RES_CAUSE2 as
+//!   ResetCause::LostHFClock or ResetCause::HFClockFrequencyError.
+//! - Reference: the reference clock of the HF supervisors is itself
+//!   supervised against the ILO.
+//! The frequency supervision counts the monitored clock over a period
+//! of reference clock cycles, the window, and acts when the count is
+//! outside the lower and upper limits.
+//! trm 20.5 Clock Supervision.
+
+#![deny(unsafe_code)]
+
+use crate::drivers::system::clocks::RootClocks;
+use crate::drivers::system::System;
+use crate::error::Error;
+
+/// ClockLossAction is the response of a supervisor to a lost clock, or
+/// for the frequency supervisors a frequency outside the limits.
+/// Interrupt reports the loss through the fault structures, the fault
+/// interrupt must be enabled to be notified.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockLossAction {
+    Ignore = 0,
+    Interrupt = 1,
+    Reset = 2,
+    InterruptAndReset = 3,
+}
+
+/// CsvLossWindow is the number of reference clock cycles without a
+/// monitored clock edge before the clock is considered lost.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsvLossWindow {
+    Cycles1 = 0,
+    Cycles2 = 1,
+    Cycles4 = 2,
+    Cycles8 = 3,
+    Cycles16 = 4,
+    Cycles32 = 5,
+    Cycles64 = 6,
+    Cycles128 = 7,
+}
+
+/// CsvFrequencyLimits holds the period in reference clock cycles and
+/// the limits of the monitored clock count within that period.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CsvFrequencyLimits {
+    window: u16,
+    lower: u16,
+    upper: u16,
+}
+
+impl CsvFrequencyLimits {
+    /// new returns the limits for a window of reference cycles, an error
+    /// when the window is 0 or lower is above upper.
+    pub fn new(window: u16, lower: u16, upper: u16) -> Result<Self, Error> {
+        if window == 0 || lower > upper {
+            return Err(Error::CsvLimitsOutOfRange);
+        }
+        Ok(Self {
+            window,
+            lower,
+            upper,
+        })
+    }
+    /// for_frequency calculates the limits for a monitored_hz clock
+    /// checked over window cycles of a reference_hz clock, allowing the
+    /// count to vary by tolerance_ppm. The count is rounded outwards so
+    /// the nominal frequency is always accepted.
+    /// It returns an error when the count does not fit the 16 bit limits.
+    ///```
+    /// // CLK_HF0 at 100MHz against the 8MHz IMO over 80 cycles (10us), +/-5%
+    /// let limits = CsvFrequencyLimits::for_frequency(100_000_000, 8_000_000, 80, 50_000)?;
+    ///```
+    pub fn for_frequency(
+        monitored_hz: u32,
+        reference_hz: u32,
+        window: u16,
+        tolerance_ppm: u32,
+    ) -> Result<Self, Error> {
+        if reference_hz == 0 || tolerance_ppm >= 1_000_000 {
+            return Err(Error::CsvLimitsOutOfRange);
+        }
+        let count = monitored_hz as u64 * window as u64;
+        let reference = reference_hz as u64;
+        let lower = count * (1_000_000 - tolerance_ppm as u64) / (1_000_000 * reference);
+        let upper = (count * (1_000_000 + tolerance_ppm as u64) + 1_000_000 * reference - 1)
+            / (1_000_000 * reference);
+        if upper > u16::MAX as u64 {
+            return Err(Error::CsvLimitsOutOfRange);
+        }
+        Self::new(window, lower as u16, upper as u16)
+    }
+    #[inline(always)]
+    pub fn window(&self) -> u16 {
+        self.window
+    }
+    #[inline(always)]
+    pub fn lower(&self) -> u16 {
+        self.lower
+    }
+    #[inline(always)]
+    pub fn upper(&self) -> u16 {
+        self.upper
+    }
+}
+
+impl System {
+    /// configure_wco_supervisor arms the WCO clock supervisor. The
+    /// supervisor is clocked by the ILO which must be running.
+    /// Configuration is only possible while the supervisor is disabled
+    /// so it is disabled first.
+    #[allow(unsafe_code)]
+    pub fn configure_wco_supervisor(&self, window: CsvLossWindow, action: ClockLossAction) -> () {
+        self.disable_wco_supervisor();
+        //Safety: enum discriminants are within the field widths.
+        self.srss.clk_csv_wco_ctl.modify(|_, w| unsafe {
+            w.csv_loss_window()
+                .bits(window as u8)
+                .csv_loss_action()
+                .bits(action as u8)
+        });
+        self.srss.clk_csv_wco_ctl.modify(|_, w| w.csv_en().set_bit());
+    }
+    #[inline(always)]
+    pub fn disable_wco_supervisor(&self) -> () {
+        self.srss.clk_csv_wco_ctl.modify(|_, w| w.csv_en().clear_bit());
+    }
+
+    /// configure_hf_loss_supervisor arms the loss of clock supervisor of
+    /// a CLK_HF root, the root is lost when no edge is seen within
+    /// window reference cycles. The frequency supervisor is left as is.
+    #[allow(unsafe_code)]
+    pub fn configure_hf_loss_supervisor(
+        &self,
+        root: RootClocks,
+        window: CsvLossWindow,
+        action: ClockLossAction,
+    ) -> () {
+        let csv = &self.srss.clk_csv[root as usize];
+        csv.hf_ctl.modify(|_, w| w.csv_loss_en().clear_bit());
+        //Safety: enum discriminants are within the field widths.
+        csv.hf_ctl.modify(|_, w| unsafe {
+            w.csv_loss_window()
+                .bits(window as u8)
+                .csv_loss_action()
+                .bits(action as u8)
+        });
+        csv.hf_ctl.modify(|_, w| w.csv_loss_en().set_bit());
+    }
+    /// configure_hf_frequency_supervisor arms the frequency supervisor
+    /// of a CLK_HF root. With one_shot the supervisor checks a single
+    /// period and stops, otherwise it checks continuously.
+    /// The loss supervisor must be armed with the frequency supervisor,
+    /// a stopped clock produces no count to check.
+    #[allow(unsafe_code)]
+    pub fn configure_hf_frequency_supervisor(
+        &self,
+        root: RootClocks,
+        limits: CsvFrequencyLimits,
+        action: ClockLossAction,
+        one_shot: bool,
+    ) -> () {
+        let csv = &self.srss.clk_csv[root as usize];
+        csv.hf_ctl.modify(|_, w| w.csv_freq_en().clear_bit());
+        //Safety: the limits are 16 bit fields, the action within its field width.
+        csv.hf_limit.write(|w| unsafe {
+            w.lower_limit()
+                .bits(limits.lower)
+                .upper_limit()
+                .bits(limits.upper)
+        });
+        csv.hf_ctl.modify(|_, w| unsafe {
+            w.csv_freq_window()
+                .bits(limits.window - 1)
+                .csv_freq_action()
+                .bits(action as u8)
+                .csv_freq_one_shot()
+                .bit(one_shot)
+        });
+        csv.hf_ctl.modify(|_, w| w.csv_freq_en().set_bit());
+    }
+    /// disable_hf_supervisor disables the loss and frequency supervisors
+    /// of a CLK_HF root. They must be disabled before the root is
+    /// switched to another path or its frequency is changed.
+    #[inline(always)]
+    pub fn disable_hf_supervisor(&self, root: RootClocks) -> () {
+        self.srss.clk_csv[root as usize]
+            .hf_ctl
+            .modify(|_, w| w.csv_loss_en().clear_bit().csv_freq_en().clear_bit());
+    }
+    /// configure_reference_supervisor arms the supervisor of the
+    /// reference clock of the HF supervisors. The reference is counted
+    /// over a period of the ILO which must be running, the limits apply
+    /// to that count. startup_cycles is the number of ILO cycles ignored
+    /// after enabling while the counters settle.
+    #[allow(unsafe_code)]
+    pub fn configure_reference_supervisor(
+        &self,
+        startup_cycles: u16,
+        limits: CsvFrequencyLimits,
+        action: ClockLossAction,
+    ) -> () {
+        self.disable_reference_supervisor();
+        //Safety: the limits are 16 bit fields, the action within its field width.
+        self.srss.clk_csv_ref_limit.write(|w| unsafe {
+            w.lower_limit()
+                .bits(limits.lower)
+                .upper_limit()
+                .bits(limits.upper)
+        });
+        self.srss.clk_csv_ref_ctl.modify(|_, w| unsafe {
+            w.csv_startup()
+                .bits(startup_cycles)
+                .csv_action()
+                .bits(action as u8)
+        });
+        self.srss.clk_csv_ref_ctl.modify(|_, w| w.csv_en().set_bit());
+    }
+    #[inline(always)]
+    pub fn disable_reference_supervisor(&self) -> () {
+        self.srss.clk_csv_ref_ctl.modify(|_, w| w.csv_en().clear_bit());
+    }
+}
@@ -0,0 +1,184 @@
+//! eco.rs implements the external crystal oscillator (ECO).
+//! The ECO drives a 16-35MHz crystal connected to the ECO_IN and
+//! ECO_OUT pins (P12.6 and P12.7) which must be left in the analog
+//! HighZ state. The oscillator trims are calculated from the crystal
+//! parameters, trm 20.2.4 External Crystal Oscillator:
+//! - max amplitude = sqrt(drive_level / 2 * esr) / (pi * freq * load_cap)
+//! - gm_min = 16 * (2 * pi * freq)^2 * esr * load_cap^2
+//!
+
+#![deny(unsafe_code)]
+
+use crate::drivers::system::clocks::{ClockPath, PathSource};
+use crate::drivers::system::startup::{block_on, EcoStartup};
+use crate::drivers::system::System;
+use crate::error::Error;
+
+pub const ECO_MIN_FREQ: u32 = 16_000_000;
+pub const ECO_MAX_FREQ: u32 = 35_000_000;
+// Minimum amplitude the ECO can be trimmed to in mV.
+const ECO_MIN_AMPLITUDE: u32 = 500;
+// Each gm stage provides 4.5mA/V, a margin of 5 times gm_min is used.
+const ECO_GM_PER_STAGE: u64 = 4_500;
+const ECO_GM_MARGIN: u64 = 5;
+const ECO_MAX_GM_STAGES: u64 = 4;
+
+/// EcoConfig holds the crystal parameters and the trim values
+/// calculated from them for CLK_TRIM_ECO_CTL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EcoConfig {
+    freq_hz: u32,
+    wdtrim: u8, // amplitude detector threshold 0-7
+    atrim: u8,  // amplitude 0-15
+    ftrim: u8,  // filter 0-3
+    rtrim: u8,  // feedback resistor 0-3
+    gtrim: u8,  // gm stages 0-3
+}
+
+impl EcoConfig {
+    /// new calculates the ECO trims for a crystal from:
+    ///  - freq_hz: the crystal frequency 16-35MHz.
+    ///  - load_cap_pf: the crystal load capacitance in pF.
+    ///  - esr_ohm: the crystal equivalent series resistance in ohms.
+    ///  - drive_level_uw: the maximum crystal drive level in uW.
+    /// It returns an error when the frequency is out of range, or the
+    /// crystal cannot be driven, i.e. its maximum amplitude is below
+    /// 500mV or it needs more than 4 gm stages.
+    ///```
+    /// let eco_config = EcoConfig::new(17_203_200, 18, 50, 100)?;
+    ///```
+    pub fn new(freq_hz: u32, load_cap_pf: u32, esr_ohm: u32, drive_level_uw: u32) -> Result<Self, Error> {
+        if freq_hz < ECO_MIN_FREQ || freq_hz > ECO_MAX_FREQ {
+            return Err(Error::EcoFrequencyOutOfRange);
+        }
+        if load_cap_pf == 0 || esr_ohm == 0 || drive_level_uw == 0 {
+            return Err(Error::EcoCouldNotBeConfigured);
+        }
+        let freq = freq_hz as u64;
+        let cap = load_cap_pf as u64;
+        let esr = esr_ohm as u64;
+        let drive = drive_level_uw as u64;
+
+        // Maximum amplitude in mV, pi scaled by 1000.
+        let amplitude =
+            (1_000_000_000_000 * isqrt(drive * 1_000_000 / (2 * esr)) / (3142 * freq * cap)) as u32;
+        if amplitude < ECO_MIN_AMPLITUDE {
+            return Err(Error::EcoCouldNotBeConfigured);
+        }
+        // gm_min in uA/V, 16 * (2 * pi)^2 = 631.654.
+        let freq_khz = freq / 1000;
+        let gm_min = 631_654 * freq_khz * freq_khz / 1_000_000_000 * esr * cap * cap / 1_000_000;
+        let stages = div_ceil(gm_min * ECO_GM_MARGIN, ECO_GM_PER_STAGE).max(1);
+        if stages > ECO_MAX_GM_STAGES {
+            return Err(Error::EcoCouldNotBeConfigured);
+        }
+
+        // Amplitude is set in 50mV steps from 500mV without exceeding the
+        // maximum, the detector threshold is set to half the amplitude.
+        let atrim = ((amplitude - ECO_MIN_AMPLITUDE) / 50).min(15);
+        let target_amplitude = ECO_MIN_AMPLITUDE + 50 * atrim;
+        let wdtrim = ((target_amplitude / 2).saturating_sub(50) / 50).min(7);
+        let rtrim = if freq_hz > 28_600_000 {
+            0
+        } else if freq_hz > 23_330_000 {
+            1
+        } else if freq_hz > 16_500_000 {
+            2
+        } else {
+            3
+        };
+
+        Ok(Self {
+            freq_hz,
+            wdtrim: wdtrim as u8,
+            atrim: atrim as u8,
+            ftrim: 3,
+            rtrim,
+            gtrim: (stages - 1) as u8,
+        })
+    }
+    #[inline(always)]
+    pub fn freq(&self) -> u32 {
+        self.freq_hz
+    }
+}
+
+impl System {
+    /// configure_eco writes the ECO trims. The trims can only be changed
+    /// while the ECO is disabled so the ECO is stopped first.
+    #[allow(unsafe_code)]
+    pub fn configure_eco(&self, eco_config: &EcoConfig) -> () {
+        self.stop_eco();
+        //Safety: EcoConfig can only be built with trims within the field widths.
+        self.srss.clk_trim_eco_ctl.modify(|_, w| unsafe {
+            w.wdtrim()
+                .bits(eco_config.wdtrim)
+                .atrim()
+                .bits(eco_config.atrim)
+                .ftrim()
+                .bits(eco_config.ftrim)
+                .rtrim()
+                .bits(eco_config.rtrim)
+                .gtrim()
+                .bits(eco_config.gtrim)
+        });
+        //Automatic gain control keeps the crystal within its drive level.
+        self.srss.clk_eco_config.modify(|_, w| w.agc_en().set_bit());
+    }
+    /// start_eco enables the ECO and waits for it to become ready, see
+    /// EcoStartup for a non-blocking version.
+    /// timeout_us is the startup timeout in (approximately) microseconds,
+    /// crystals typically need several milliseconds to start.
+    /// On timeout the ECO is disabled again and an error returned.
+    pub fn start_eco(&self, timeout_us: u32) -> Result<(), Error> {
+        let mut startup = EcoStartup::new(timeout_us);
+        block_on(|elapsed_us| startup.poll(self, elapsed_us))
+    }
+    /// start_eco_or_imo starts the ECO and selects it as the source of
+    /// clk_path. When the ECO fails to start the IMO is selected for
+    /// clk_path instead and the startup error is returned so the
+    /// application knows it is running from the less accurate IMO.
+    pub fn start_eco_or_imo(&self, clk_path: ClockPath, timeout_us: u32) -> Result<(), Error> {
+        match self.start_eco(timeout_us) {
+            Ok(()) => {
+                self.configure_path_source(clk_path, PathSource::Eco);
+                Ok(())
+            }
+            Err(e) => {
+                self.configure_path_source(clk_path, PathSource::Imo);
+                Err(e)
+            }
+        }
+    }
+    /// stop_eco disables the ECO. Any path using the ECO must be
+    /// switched to another source first.
+    #[inline(always)]
+    pub fn stop_eco(&self) -> () {
+        self.srss.clk_eco_config.modify(|_, w| w.eco_en().clear_bit());
+    }
+    /// eco_is_ok returns true while the ECO amplitude is above the
+    /// detector threshold.
+    #[inline(always)]
+    pub fn eco_is_ok(&self) -> bool {
+        self.srss.clk_eco_status.read().eco_ok().bit_is_set()
+    }
+}
+
+#[inline(always)]
+fn div_ceil(num: u64, den: u64) -> u64 {
+    (num + den - 1) / den
+}
+
+/// isqrt returns the integer square root, no_std has no f32::sqrt.
+fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
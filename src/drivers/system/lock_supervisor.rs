@@ -0,0 +1,180 @@
+//! lock_supervisor.rs implements a software supervisor of the FLL and
+//! PLL lock status. start_fll and start_pll only wait for the initial
+//! lock, a loop can lose lock later, e.g. when its reference stops or
+//! drifts. The supervisor is polled, e.g. from a periodic timer
+//! interrupt, and on a lock loss moves the CLK_HF roots selected from
+//! the affected path to a safe path running from the IMO.
+//! The roots keep their dividers so they run slower, never faster,
+//! while on the safe path and the wait states remain valid. The Clocks
+//! record no longer describes the hardware until the loop is re-locked.
+//!```
+//! fn on_lock_loss(lost: LockedLoop) { /* record the event, lower the uart baud rate ... */ }
+//! let mut lock_supervisor = LockSupervisor::new(&system, ClockPath::Path4, Some(on_lock_loss))?;
+//! // periodically
+//! if let Some(lost) = lock_supervisor.poll(&system) {
+//!     lock_supervisor.relock(&system, lost, 200_000)?;
+//! }
+//!```
+
+#![deny(unsafe_code)]
+
+use crate::drivers::system::clocks::{ClockPath, PathSource, RootClocks};
+use crate::drivers::system::System;
+use crate::error::Error;
+
+/// LockedLoop identifies the FLL or PLL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockedLoop {
+    Fll = 0, // on CLK_PATH0
+    Pll = 1, // on CLK_PATH1
+}
+
+/// LockLossCallback is called from poll when a loop loses lock, after
+/// the roots have been moved to the safe path.
+pub type LockLossCallback = fn(LockedLoop);
+
+const ROOTS: [RootClocks; 5] = [
+    RootClocks::CpuPeriAhb,
+    RootClocks::PdmPcmI2s,
+    RootClocks::Smif,
+    RootClocks::Usb,
+    RootClocks::ClkOut,
+];
+const PATHS: [ClockPath; 5] = [
+    ClockPath::Path0,
+    ClockPath::Path1,
+    ClockPath::Path2,
+    ClockPath::Path3,
+    ClockPath::Path4,
+];
+
+/// LockSupervisor holds the safe path and the roots moved to it for
+/// each loop.
+pub struct LockSupervisor {
+    safe_path: ClockPath,
+    callback: Option<LockLossCallback>,
+    lost: [bool; 2],
+    moved: [[bool; 5]; 2],
+}
+
+impl LockSupervisor {
+    /// new selects the IMO as the source of safe_path, one of CLK_PATH2-4
+    /// which must not be used by any other root, and clears the sticky
+    /// unlock flags.
+    /// It returns an error when safe_path is CLK_PATH0 or CLK_PATH1.
+    pub fn new(
+        system: &System,
+        safe_path: ClockPath,
+        callback: Option<LockLossCallback>,
+    ) -> Result<Self, Error> {
+        match safe_path {
+            ClockPath::Path0 | ClockPath::Path1 => return Err(Error::LockSupervisorInvalidSafePath),
+            _ => {}
+        }
+        system.configure_path_source(safe_path, PathSource::Imo);
+        system.clear_unlock_occurred(LockedLoop::Fll);
+        system.clear_unlock_occurred(LockedLoop::Pll);
+        Ok(LockSupervisor {
+            safe_path,
+            callback,
+            lost: [false; 2],
+            moved: [[false; 5]; 2],
+        })
+    }
+    /// poll checks both loops and on a new lock loss moves the affected
+    /// roots to the safe path and calls the callback. It returns the
+    /// loop that lost lock, the FLL first when both did.
+    pub fn poll(&mut self, system: &System) -> Option<LockedLoop> {
+        let mut result = None;
+        for lp in [LockedLoop::Pll, LockedLoop::Fll].iter() {
+            if self.lost[*lp as usize] || !system.lock_lost(*lp) {
+                continue;
+            }
+            let path = PATHS[*lp as usize];
+            for (root, moved) in self.moved[*lp as usize].iter_mut().enumerate() {
+                if system.root_clk_is_enabled(ROOTS[root]) && system.root_clk_source(ROOTS[root]) == path as u8 {
+                    system.configure_root_clk_source(ROOTS[root], self.safe_path);
+                    *moved = true;
+                }
+            }
+            self.lost[*lp as usize] = true;
+            if let Some(callback) = self.callback {
+                callback(*lp);
+            }
+            result = Some(*lp);
+        }
+        result
+    }
+    /// is_lost returns true from a lock loss until the loop is re-locked.
+    #[inline(always)]
+    pub fn is_lost(&self, lp: LockedLoop) -> bool {
+        self.lost[lp as usize]
+    }
+    /// relock restarts the loop with its current configuration and on
+    /// lock moves the roots back from the safe path.
+    /// On failure the roots stay on the safe path and relock can be
+    /// tried again later.
+    pub fn relock(&mut self, system: &System, lp: LockedLoop, timeout_us: u32) -> Result<(), Error> {
+        match lp {
+            LockedLoop::Fll => {
+                system.stop_fll();
+                system.start_fll(timeout_us)?;
+            }
+            LockedLoop::Pll => {
+                system.stop_pll();
+                system.start_pll(timeout_us)?;
+            }
+        }
+        system.clear_unlock_occurred(lp);
+        let path = PATHS[lp as usize];
+        for (root, moved) in self.moved[lp as usize].iter_mut().enumerate() {
+            if *moved {
+                system.configure_root_clk_source(ROOTS[root], path);
+                *moved = false;
+            }
+        }
+        self.lost[lp as usize] = false;
+        Ok(())
+    }
+}
+
+impl System {
+    /// lock_lost returns true when an enabled loop is not locked or has
+    /// lost lock since the unlock flag was last cleared.
+    pub fn lock_lost(&self, lp: LockedLoop) -> bool {
+        match lp {
+            LockedLoop::Fll => {
+                let status = self.srss.clk_fll_status.read();
+                self.srss.clk_fll_config.read().fll_enable().bit_is_set()
+                    && (status.locked().bit_is_clear() || status.unlock_occurred().bit_is_set())
+            }
+            LockedLoop::Pll => {
+                let status = self.srss.clk_pll_status[0].read();
+                self.srss.clk_pll_config[0].read().enable().bit_is_set()
+                    && (status.locked().bit_is_clear() || status.unlock_occurred().bit_is_set())
+            }
+        }
+    }
+    /// clear_unlock_occurred clears the sticky UNLOCK_OCCURRED flag.
+    #[inline(always)]
+    pub fn clear_unlock_occurred(&self, lp: LockedLoop) -> () {
+        match lp {
+            LockedLoop::Fll => self
+                .srss
+                .clk_fll_status
+                .modify(|_, w| w.unlock_occurred().set_bit()),
+            LockedLoop::Pll => self.srss.clk_pll_status[0].modify(|_, w| w.unlock_occurred().set_bit()),
+        }
+    }
+    #[inline(always)]
+    pub fn root_clk_is_enabled(&self, hf_index: RootClocks) -> bool {
+        // CLK_HF0 is always enabled.
+        hf_index == RootClocks::CpuPeriAhb
+            || self.srss.clk_root_select[hf_index as usize].read().enable().bit_is_set()
+    }
+    /// root_clk_source returns the path number a root is selected from.
+    #[inline(always)]
+    pub fn root_clk_source(&self, hf_index: RootClocks) -> u8 {
+        self.srss.clk_root_select[hf_index as usize].read().root_mux().bits()
+    }
+}
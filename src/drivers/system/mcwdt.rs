@@ -0,0 +1,302 @@
+//! mcwdt.rs implements the two multi-counter watchdogs (MCWDT), trm 22.3.
+//! Each MCWDT has three counters clocked by LFCLK:
+//! - C0 and C1, 16-bit counters with a match value, the match can raise
+//!   the MCWDT interrupt or reset the device.
+//! - C2, a 32-bit free running counter raising the interrupt when a
+//!   selected bit toggles.
+//! C0 can be cascaded into C1 and C1 into C2 for long periods.
+//! The MCWDTs keep counting in DeepSleep and their interrupts,
+//! SRSS_INTERRUPT_MCWDT_0/1, wake the device, so an MCWDT is a low-power
+//! periodic wakeup source. Used in the reset mode an MCWDT is a
+//! secondary watchdog next to the WDT, see start_watchdog.
+//! Each MCWDT is an owned handle taken once from the System.
+//!```
+//! let (mut mcwdt0, _mcwdt1) = system.take_mcwdts().unwrap();
+//! // Wake every 100ms with a 32.768kHz LFCLK.
+//! mcwdt0.configure_c0(McwdtMode::Interrupt, 3277, true);
+//! mcwdt0.listen(McwdtCounter::C0);
+//! mcwdt0.enable(McwdtCounter::C0);
+//!```
+
+#![deny(unsafe_code)]
+
+use core::cell::Cell;
+
+use cortex_m::interrupt::{free, Mutex};
+
+use crate::drivers::cpuss::interrupt::InterruptSource;
+use crate::drivers::system::System;
+use crate::error::Error;
+use crate::pac::SRSS;
+
+static MCWDTS_TAKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// McwdtCounter identifies a counter of an MCWDT.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum McwdtCounter {
+    C0 = 0,
+    C1 = 1,
+    C2 = 2,
+}
+
+/// McwdtMode is the WDT_MODE encoding of the action on a C0 or C1 match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum McwdtMode {
+    Nothing = 0,
+    Interrupt = 1,
+    Reset = 2,
+    /// InterruptThenReset raises the interrupt and resets the device on
+    /// the third match with the interrupt still pending.
+    InterruptThenReset = 3,
+}
+
+/// Mcwdt is one of the two multi-counter watchdogs.
+pub struct Mcwdt {
+    index: usize,
+}
+
+impl System {
+    /// take_mcwdts returns the two MCWDTs, once.
+    pub fn take_mcwdts(&self) -> Option<(Mcwdt, Mcwdt)> {
+        free(|cs| {
+            let taken = MCWDTS_TAKEN.borrow(cs);
+            match taken.get() {
+                true => None,
+                false => {
+                    taken.set(true);
+                    Some((Mcwdt { index: 0 }, Mcwdt { index: 1 }))
+                }
+            }
+        })
+    }
+}
+
+impl Mcwdt {
+    /// configure_c0 sets the C0 match action and value. With
+    /// clear_on_match the counter restarts from 0 after the match,
+    /// giving a period of match_value + 1 LFCLK cycles.
+    /// C0 must be disabled.
+    pub fn configure_c0(&mut self, mode: McwdtMode, match_value: u16, clear_on_match: bool) -> () {
+        let mcwdt = self.regs();
+        self.unlocked(|| {
+            mcwdt
+                .mcwdt_match
+                .modify(|_, w| w.wdt_match0().bits(match_value));
+            mcwdt.mcwdt_config.modify(|_, w| {
+                w.wdt_mode0()
+                    .bits(mode as u8)
+                    .wdt_clear0()
+                    .bit(clear_on_match)
+            });
+        });
+    }
+    /// configure_c1 sets the C1 match action and value, see
+    /// configure_c0. With cascade C1 counts the C0 matches instead of
+    /// LFCLK cycles.
+    /// C1 must be disabled.
+    pub fn configure_c1(
+        &mut self,
+        mode: McwdtMode,
+        match_value: u16,
+        clear_on_match: bool,
+        cascade: bool,
+    ) -> () {
+        let mcwdt = self.regs();
+        self.unlocked(|| {
+            mcwdt
+                .mcwdt_match
+                .modify(|_, w| w.wdt_match1().bits(match_value));
+            mcwdt.mcwdt_config.modify(|_, w| {
+                w.wdt_mode1()
+                    .bits(mode as u8)
+                    .wdt_clear1()
+                    .bit(clear_on_match)
+                    .wdt_cascade0_1()
+                    .bit(cascade)
+            });
+        });
+    }
+    /// set_c0_match changes the C0 match value while C0 runs, the new
+    /// value takes effect after up to three LFCLK cycles.
+    pub fn set_c0_match(&mut self, match_value: u16) -> () {
+        let mcwdt = self.regs();
+        self.unlocked(|| {
+            mcwdt
+                .mcwdt_match
+                .modify(|_, w| w.wdt_match0().bits(match_value));
+        });
+    }
+    /// configure_c2 selects the C2 bit, 0..=31, whose toggle raises the
+    /// interrupt when interrupt is true, a period of 2^(bit + 1) counts.
+    /// With cascade C2 counts the C1 matches instead of LFCLK cycles.
+    /// C2 must be disabled.
+    #[allow(unsafe_code)]
+    pub fn configure_c2(&mut self, interrupt: bool, bit: u8, cascade: bool) -> () {
+        let mcwdt = self.regs();
+        self.unlocked(|| {
+            //Safety: WDT_BITS2 is 5 bits wide, bit is masked.
+            mcwdt.mcwdt_config.modify(|_, w| unsafe {
+                w.wdt_mode2()
+                    .bit(interrupt)
+                    .wdt_bits2()
+                    .bits(bit & 0x1f)
+                    .wdt_cascade1_2()
+                    .bit(cascade)
+            });
+        });
+    }
+    /// enable starts a counter and waits until it runs, up to three
+    /// LFCLK cycles.
+    pub fn enable(&mut self, counter: McwdtCounter) -> () {
+        let mcwdt = self.regs();
+        self.unlocked(|| match counter {
+            McwdtCounter::C0 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_enable0().set_bit()),
+            McwdtCounter::C1 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_enable1().set_bit()),
+            McwdtCounter::C2 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_enable2().set_bit()),
+        });
+        while !self.is_enabled(counter) {}
+    }
+    /// disable stops a counter and waits until it has stopped.
+    pub fn disable(&mut self, counter: McwdtCounter) -> () {
+        let mcwdt = self.regs();
+        self.unlocked(|| match counter {
+            McwdtCounter::C0 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_enable0().clear_bit()),
+            McwdtCounter::C1 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_enable1().clear_bit()),
+            McwdtCounter::C2 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_enable2().clear_bit()),
+        });
+        while self.is_enabled(counter) {}
+    }
+    /// is_enabled returns true while a counter runs.
+    pub fn is_enabled(&self, counter: McwdtCounter) -> bool {
+        let ctl = self.regs().mcwdt_ctl.read();
+        match counter {
+            McwdtCounter::C0 => ctl.wdt_enabled0().bit_is_set(),
+            McwdtCounter::C1 => ctl.wdt_enabled1().bit_is_set(),
+            McwdtCounter::C2 => ctl.wdt_enabled2().bit_is_set(),
+        }
+    }
+    /// reset_counter sets a counter to 0, for C0 and C1 in the reset mode
+    /// this feeds the watchdog.
+    pub fn reset_counter(&mut self, counter: McwdtCounter) -> () {
+        let mcwdt = self.regs();
+        self.unlocked(|| match counter {
+            McwdtCounter::C0 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_reset0().set_bit()),
+            McwdtCounter::C1 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_reset1().set_bit()),
+            McwdtCounter::C2 => mcwdt.mcwdt_ctl.modify(|_, w| w.wdt_reset2().set_bit()),
+        });
+    }
+    #[inline(always)]
+    pub fn c0(&self) -> u16 {
+        self.regs().mcwdt_cntlow.read().wdt_ctr0().bits()
+    }
+    #[inline(always)]
+    pub fn c1(&self) -> u16 {
+        self.regs().mcwdt_cntlow.read().wdt_ctr1().bits()
+    }
+    #[inline(always)]
+    pub fn c2(&self) -> u32 {
+        self.regs().mcwdt_cnthigh.read().wdt_ctr2().bits()
+    }
+
+    /// listen forwards the events of a counter to the MCWDT interrupt.
+    #[allow(unsafe_code)]
+    pub fn listen(&mut self, counter: McwdtCounter) -> () {
+        //Safety: the mask register belongs to this MCWDT.
+        self.regs()
+            .mcwdt_intr_mask
+            .modify(|r, w| unsafe { w.bits(r.bits() | 1 << counter as u32) });
+    }
+    /// unlisten stops forwarding the events of a counter.
+    #[allow(unsafe_code)]
+    pub fn unlisten(&mut self, counter: McwdtCounter) -> () {
+        //Safety: the mask register belongs to this MCWDT.
+        self.regs()
+            .mcwdt_intr_mask
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << counter as u32)) });
+    }
+    #[inline(always)]
+    pub fn is_pending(&self, counter: McwdtCounter) -> bool {
+        self.regs().mcwdt_intr.read().bits() & 1 << counter as u32 != 0
+    }
+    /// clear_interrupt clears the event of a counter, the read back
+    /// ensures the clear has completed before the interrupt returns.
+    #[allow(unsafe_code)]
+    pub fn clear_interrupt(&mut self, counter: McwdtCounter) -> () {
+        //Safety: INTR bits are cleared by writing 1.
+        self.regs()
+            .mcwdt_intr
+            .write(|w| unsafe { w.bits(1 << counter as u32) });
+        self.regs().mcwdt_intr.read();
+    }
+    /// interrupt returns the interrupt source of the MCWDT.
+    #[inline(always)]
+    pub fn interrupt(&self) -> InterruptSource {
+        match self.index {
+            0 => InterruptSource::SRSS_INTERRUPT_MCWDT_0,
+            _ => InterruptSource::SRSS_INTERRUPT_MCWDT_1,
+        }
+    }
+
+    /// start_watchdog starts C0, cascaded into C1 for long timeouts, to
+    /// reset the device timeout_ms after the last feed. The timeout is
+    /// calculated from an LFCLK of lfclk_hz, rounded up to an LFCLK
+    /// cycle, and can be up to 0xffff * 2^16 LFCLK cycles, 36 hours at
+    /// 32.768kHz. The counters start at 0 and reset the device when the
+    /// count reaches the match value:
+    /// - Up to 0xffff cycles, 2s at 32.768kHz, C0 alone counts the
+    ///   timeout exactly.
+    /// - Longer timeouts are counted by C1 in periods of 2^n cycles of
+    ///   C0, the smallest period keeping the C1 match in 16 bits, and
+    ///   rounded up to a whole period.
+    /// It returns an error when the timeout is out of range.
+    pub fn start_watchdog(&mut self, timeout_ms: u32, lfclk_hz: u32) -> Result<(), Error> {
+        let ticks = (timeout_ms as u64 * lfclk_hz as u64 + 999) / 1000;
+        if ticks == 0 || ticks > 0xffff << 16 {
+            return Err(Error::McwdtTimeoutOutOfRange);
+        }
+        self.disable(McwdtCounter::C0);
+        self.disable(McwdtCounter::C1);
+        if ticks <= 0xffff {
+            self.configure_c1(McwdtMode::Nothing, 0, false, false);
+            self.configure_c0(McwdtMode::Reset, ticks as u16, false);
+        } else {
+            // C0 divides LFCLK by a power of two so C1 counts the
+            // rounded up remainder in 16 bits.
+            let mut shift = 1;
+            while (ticks + (1 << shift) - 1) >> shift > 0xffff {
+                shift += 1;
+            }
+            let periods = (ticks + (1 << shift) - 1) >> shift;
+            self.configure_c0(McwdtMode::Nothing, ((1u32 << shift) - 1) as u16, true);
+            self.configure_c1(McwdtMode::Reset, periods as u16, false, true);
+            self.reset_counter(McwdtCounter::C1);
+            self.enable(McwdtCounter::C1);
+        }
+        self.reset_counter(McwdtCounter::C0);
+        self.enable(McwdtCounter::C0);
+        Ok(())
+    }
+    /// feed restarts the watchdog timeout started by start_watchdog.
+    pub fn feed(&mut self) -> () {
+        self.reset_counter(McwdtCounter::C0);
+        self.reset_counter(McwdtCounter::C1);
+    }
+
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    fn regs(&self) -> &'static crate::pac::srss::MCWDT_STRUCT {
+        //Safety: each MCWDT_STRUCT belongs to a single handle.
+        unsafe { &(*SRSS::PTR).mcwdt_struct[self.index] }
+    }
+    /// unlocked runs f with the MCWDT_LOCK cleared, the CTL, CONFIG and
+    /// MATCH registers are write protected while locked.
+    fn unlocked<F: FnOnce()>(&self, f: F) -> () {
+        let mcwdt = self.regs();
+        free(|_| {
+            mcwdt.mcwdt_lock.modify(|_, w| w.mcwdt_lock().clr0());
+            mcwdt.mcwdt_lock.modify(|_, w| w.mcwdt_lock().clr1());
+            f();
+            mcwdt.mcwdt_lock.modify(|_, w| w.mcwdt_lock().set01());
+        });
+    }
+}
@@ -7,6 +7,7 @@
 //! - Reset Cause -- reset_cause
 //! - I/O system -- io_sys
 //! - Watchdog -- watchdog
+//! - Multi-counter watchdogs -- mcwdt
 //! - Trigger Multiplexer -- trigger_mux
 //! - Profiler -- profiler
 
@@ -14,14 +15,31 @@ pub use clocks::{
     ClockPath, Clocks, Divider, DsiMux, FllCco, FllConfig, FllGain, FllOutputDiv,
     PathSource, RootClocks, SelectChannelNumber,
 };
+pub use clk_cal::MeasureClock;
+pub use clock_tree::{ClockTree, ClockTreeRegs, SourceFreqs};
+pub use csv::{ClockLossAction, CsvFrequencyLimits, CsvLossWindow};
+pub use eco::EcoConfig;
+pub use lock_supervisor::{LockSupervisor, LockedLoop};
+pub use mcwdt::{Mcwdt, McwdtCounter, McwdtMode};
+pub use pll::PllConfig;
 pub use reset_cause::ResetCause;
+pub use startup::{EcoStartup, FllStartup, PllStartup};
 
 use crate::pac::SRSS;
 
+pub mod clk_cal;
+pub mod clock_tree;
 pub mod clocks;
+pub mod csv;
+pub mod eco;
 pub mod interrupts;
+pub mod lock_supervisor;
+pub mod mcwdt;
+pub mod pilo;
+pub mod pll;
 pub mod power_sam;
 pub mod reset_cause;
+pub mod startup;
 pub mod watchdog;
 
 pub struct System {
@@ -0,0 +1,119 @@
+//! pilo.rs implements the precision internal low-speed oscillator
+//! (PILO), a 32.768kHz oscillator more accurate than the ILO that can
+//! clock LFCLK on boards without a watch crystal.
+//! The PILO frequency is set by CLK_PILO_CONFIG.PILO_FFREQ, a higher
+//! value giving a higher frequency. It can be trimmed against the WCO,
+//! e.g. once in production, and the trim stored for later boots.
+//! trm 20.2.6 Precision Internal Low-Speed Oscillator.
+
+#![deny(unsafe_code)]
+
+use cortex_m::asm::delay;
+
+use crate::clocks::{PILO_FREQ, WCO_FREQ};
+use crate::drivers::system::clk_cal::MeasureClock;
+use crate::drivers::system::System;
+use crate::error::Error;
+
+const PILO_FFREQ_MAX: u16 = 0x3ff; // 10 bits
+const PILO_STARTUP_US: u32 = 1_000;
+// Number of PILO cycles per measurement when trimming, 8192 cycles
+// (250ms) gives a resolution of ~120ppm against the WCO.
+const PILO_MEASURE_CYCLES: u32 = 8192;
+const PILO_MEASURE_TIMEOUT_US: u32 = 500_000;
+
+impl System {
+    /// enable_pilo starts the PILO, the oscillator needs 1ms to start
+    /// before its output is enabled, see trm 20.2.6.
+    /// clk_hf0_hz is the current CLK_HF0 frequency, the CPU clocks are
+    /// no faster so the wait lasts at least 1ms.
+    pub fn enable_pilo(&self, clk_hf0_hz: u32) -> () {
+        if self.pilo_is_enabled() {
+            return;
+        }
+        self.srss.clk_pilo_config.modify(|_, w| w.pilo_en().set_bit());
+        delay(clk_hf0_hz / 1_000_000 * PILO_STARTUP_US + PILO_STARTUP_US);
+        self.srss
+            .clk_pilo_config
+            .modify(|_, w| w.pilo_reset_n().set_bit().pilo_clk_en().set_bit());
+    }
+    /// disable_pilo stops the PILO, LFCLK must be switched to another
+    /// source first.
+    #[inline(always)]
+    pub fn disable_pilo(&self) -> () {
+        self.srss.clk_pilo_config.modify(|_, w| {
+            w.pilo_en()
+                .clear_bit()
+                .pilo_reset_n()
+                .clear_bit()
+                .pilo_clk_en()
+                .clear_bit()
+        });
+    }
+    #[inline(always)]
+    pub fn pilo_is_enabled(&self) -> bool {
+        self.srss.clk_pilo_config.read().pilo_en().bit_is_set()
+    }
+    /// pilo_trim returns the current PILO_FFREQ trim.
+    #[inline(always)]
+    pub fn pilo_trim(&self) -> u16 {
+        self.srss.clk_pilo_config.read().pilo_ffreq().bits()
+    }
+    /// set_pilo_trim writes PILO_FFREQ, e.g. a trim found by trim_pilo
+    /// on an earlier boot.
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    pub fn set_pilo_trim(&self, trim: u16) -> () {
+        //Safety: trim is masked to the 10 bit field.
+        self.srss
+            .clk_pilo_config
+            .modify(|_, w| unsafe { w.pilo_ffreq().bits(trim & PILO_FFREQ_MAX) });
+    }
+    /// trim_pilo adjusts PILO_FFREQ until the PILO is within
+    /// tolerance_ppm of 32.768kHz measured against the WCO, which must
+    /// be running, see Backup::start_wco.
+    /// The trim is found by a binary search, each step taking a 250ms
+    /// measurement. The closest trim is kept and the measured PILO
+    /// frequency returned, or an error when the closest trim is outside
+    /// tolerance_ppm.
+    pub fn trim_pilo(&self, tolerance_ppm: u32) -> Result<u32, Error> {
+        if !self.pilo_is_enabled() {
+            return Err(Error::PiloNotEnabled);
+        }
+        let measure = |trim: u16| {
+            self.set_pilo_trim(trim);
+            self.measure_clock(
+                MeasureClock::Pilo,
+                PILO_MEASURE_CYCLES,
+                MeasureClock::Wco,
+                WCO_FREQ,
+                PILO_MEASURE_TIMEOUT_US,
+            )
+        };
+        let error_ppm =
+            |freq: u32| (freq as i64 - PILO_FREQ as i64).abs() as u64 * 1_000_000 / PILO_FREQ as u64;
+
+        let current = self.pilo_trim();
+        let mut best = (current, measure(current)?);
+        let (mut low, mut high) = (0, PILO_FFREQ_MAX);
+        while low <= high && error_ppm(best.1) > tolerance_ppm as u64 {
+            let mid = low + (high - low) / 2;
+            let freq = measure(mid)?;
+            if error_ppm(freq) < error_ppm(best.1) {
+                best = (mid, freq);
+            }
+            if freq < PILO_FREQ {
+                low = mid + 1;
+            } else if mid == 0 {
+                break;
+            } else {
+                high = mid - 1;
+            }
+        }
+        self.set_pilo_trim(best.0);
+        match error_ppm(best.1) > tolerance_ppm as u64 {
+            true => Err(Error::PiloTrimOutOfTolerance),
+            false => Ok(best.1),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! pll.rs implements the phase locked loop (PLL) on CLK_PATH1.
+//! The PLL output frequency is given by:
+//! Fout = Fref * feedback_div / (reference_div * output_div)
+//! with the following constraints, trm 20.4.3 and datasheet:
+//! - Fref (the path source: IMO, ECO or EXTCLK) 4-64MHz.
+//! - Fpfd = Fref / reference_div 4-8MHz.
+//! - Fvco = Fpfd * feedback_div 170-400MHz, below 200MHz the low
+//!   frequency mode of the VCO is used.
+//! - Fout 10.625-150MHz.
+//!
+
+#![deny(unsafe_code)]
+
+use cortex_m::asm::delay;
+
+use crate::drivers::system::startup::{block_on, PllStartup};
+use crate::drivers::system::System;
+use crate::error::Error;
+
+pub const PLL_MIN_INPUT_FREQ: u32 = 4_000_000;
+pub const PLL_MAX_INPUT_FREQ: u32 = 64_000_000;
+pub const PLL_MIN_OUTPUT_FREQ: u32 = 10_625_000;
+pub const PLL_MAX_OUTPUT_FREQ: u32 = 150_000_000;
+const PLL_MIN_PFD_FREQ: u64 = 4_000_000;
+const PLL_MAX_PFD_FREQ: u64 = 8_000_000;
+const PLL_MIN_VCO_FREQ: u64 = 170_000_000;
+const PLL_MAX_VCO_FREQ: u64 = 400_000_000;
+const PLL_LF_MODE_MAX_VCO_FREQ: u64 = 200_000_000;
+const PLL_MIN_FEEDBACK_DIV: u64 = 22;
+const PLL_MAX_FEEDBACK_DIV: u64 = 112;
+const PLL_MIN_REFERENCE_DIV: u64 = 1;
+const PLL_MAX_REFERENCE_DIV: u64 = 18;
+const PLL_MIN_OUTPUT_DIV: u64 = 2;
+const PLL_MAX_OUTPUT_DIV: u64 = 16;
+
+/// PllConfig holds the divider values for CLK_PLL_CONFIG.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PllConfig {
+    feedback_div: u8,  // 22-112
+    reference_div: u8, // 1-18
+    output_div: u8,    // 2-16
+    lf_mode: bool,     // VCO runs in the 170-200MHz range.
+}
+
+impl PllConfig {
+    /// for_target selects the feedback, reference and output dividers
+    /// producing the frequency closest to target_hz from a reference_hz
+    /// path source.
+    /// Every output and reference divider is tried, the feedback divider
+    /// being the nearest integer for that pair. Ties keep the first match
+    /// found, i.e. the smallest output divider and so the lowest VCO
+    /// frequency.
+    /// It returns an error when either frequency is out of range or no
+    /// divider combination keeps the PFD and VCO within their limits.
+    ///```
+    /// // 8MHz IMO to 150MHz: 8MHz / 2 * 75 / 2, a 300MHz VCO
+    /// let pll_config = PllConfig::for_target(8_000_000, 150_000_000)?;
+    ///```
+    pub fn for_target(reference_hz: u32, target_hz: u32) -> Result<Self, Error> {
+        if reference_hz < PLL_MIN_INPUT_FREQ || reference_hz > PLL_MAX_INPUT_FREQ {
+            return Err(Error::PllReferenceFrequencyOutOfRange);
+        }
+        if target_hz < PLL_MIN_OUTPUT_FREQ || target_hz > PLL_MAX_OUTPUT_FREQ {
+            return Err(Error::PllTargetFrequencyOutOfRange);
+        }
+        let reference = reference_hz as u64;
+        let target = target_hz as u64;
+
+        let mut best: Option<(PllConfig, u64, u64)> = None; // config, error numerator, denominator
+        for output_div in PLL_MIN_OUTPUT_DIV..=PLL_MAX_OUTPUT_DIV {
+            for reference_div in PLL_MIN_REFERENCE_DIV..=PLL_MAX_REFERENCE_DIV {
+                let pfd = reference / reference_div;
+                if pfd < PLL_MIN_PFD_FREQ || pfd > PLL_MAX_PFD_FREQ {
+                    continue;
+                }
+                let den = reference_div * output_div;
+                let feedback_div = (target * den + reference / 2) / reference;
+                if feedback_div < PLL_MIN_FEEDBACK_DIV || feedback_div > PLL_MAX_FEEDBACK_DIV {
+                    continue;
+                }
+                let vco = reference * feedback_div / reference_div;
+                if vco < PLL_MIN_VCO_FREQ || vco > PLL_MAX_VCO_FREQ {
+                    continue;
+                }
+                // |reference * feedback / den - target| compared as fractions.
+                let num = (reference * feedback_div).max(target * den)
+                    - (reference * feedback_div).min(target * den);
+                let better = match best {
+                    None => true,
+                    Some((_, best_num, best_den)) => num * best_den < best_num * den,
+                };
+                if better {
+                    let config = PllConfig {
+                        feedback_div: feedback_div as u8,
+                        reference_div: reference_div as u8,
+                        output_div: output_div as u8,
+                        lf_mode: vco < PLL_LF_MODE_MAX_VCO_FREQ,
+                    };
+                    best = Some((config, num, den));
+                }
+            }
+        }
+        match best {
+            Some((config, _, _)) => Ok(config),
+            None => Err(Error::PllCouldNotBeConfigured),
+        }
+    }
+    /// output_freq returns the PLL output frequency produced by this
+    /// configuration from a reference_hz path clock.
+    pub fn output_freq(&self, reference_hz: u32) -> u32 {
+        (reference_hz as u64 * self.feedback_div as u64
+            / (self.reference_div as u64 * self.output_div as u64)) as u32
+    }
+    #[inline(always)]
+    pub fn feedback_div(&self) -> u8 {
+        self.feedback_div
+    }
+    #[inline(always)]
+    pub fn reference_div(&self) -> u8 {
+        self.reference_div
+    }
+    #[inline(always)]
+    pub fn output_div(&self) -> u8 {
+        self.output_div
+    }
+    #[inline(always)]
+    pub fn lf_mode(&self) -> bool {
+        self.lf_mode
+    }
+}
+
+impl System {
+    /// configure_pll writes the PLL dividers for CLK_PATH1. The PLL is
+    /// stopped first so CLK_PATH1 runs from its source while the
+    /// dividers change.
+    #[allow(unsafe_code)]
+    pub fn configure_pll(&self, pll_config: PllConfig) -> () {
+        //see trm 20.4.3.1 Configuring and enabling the PLL.
+        self.stop_pll();
+        //Safety: PllConfig can only be built within the divider ranges.
+        self.srss.clk_pll_config[0].modify(|_, w| unsafe {
+            w.feedback_div()
+                .bits(pll_config.feedback_div)
+                .reference_div()
+                .bits(pll_config.reference_div)
+                .output_div()
+                .bits(pll_config.output_div)
+        });
+        self.srss.clk_pll_config[0].modify(|_, w| w.pll_lf_mode().bit(pll_config.lf_mode));
+    }
+    /// start_pll enables the PLL and waits for it to lock before
+    /// switching CLK_PATH1 to the PLL output, see PllStartup for a
+    /// non-blocking version.
+    /// timeout_us is the lock timeout in (approximately) microseconds.
+    pub fn start_pll(&self, timeout_us: u32) -> Result<(), Error> {
+        let mut startup = PllStartup::new(timeout_us);
+        block_on(|elapsed_us| startup.poll(self, elapsed_us))
+    }
+    /// stop_pll switches CLK_PATH1 back to the PLL reference then
+    /// disables the PLL.
+    #[inline(always)]
+    pub fn stop_pll(&self) -> () {
+        if self.srss.clk_pll_config[0].read().enable().bit_is_set() {
+            self.srss.clk_pll_config[0].modify(|_, w| w.bypass_sel().pll_ref());
+            self.srss.clk_pll_config[0].read().bypass_sel().is_pll_ref(); // read ensures write has completed.
+            // Allow the output mux to switch before removing the PLL clock.
+            delay(1000);
+            self.srss.clk_pll_config[0].modify(|_, w| w.enable().clear_bit());
+        } else {
+            //job is done.
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pll_imo_to_150mhz() {
+        // 8MHz / 2 * 75 / 2, a 300MHz VCO.
+        let config = PllConfig::for_target(8_000_000, 150_000_000).unwrap();
+        assert_eq!(config.reference_div(), 2);
+        assert_eq!(config.feedback_div(), 75);
+        assert_eq!(config.output_div(), 2);
+        assert!(!config.lf_mode());
+        assert_eq!(config.output_freq(8_000_000), 150_000_000);
+    }
+
+    #[test]
+    fn pll_lf_mode_below_200mhz_vco() {
+        // 8MHz / 2 * 45 / 2, a 180MHz VCO.
+        let config = PllConfig::for_target(8_000_000, 90_000_000).unwrap();
+        assert_eq!(config.reference_div(), 2);
+        assert_eq!(config.feedback_div(), 45);
+        assert_eq!(config.output_div(), 2);
+        assert!(config.lf_mode());
+        assert_eq!(config.output_freq(8_000_000), 90_000_000);
+    }
+
+    #[test]
+    fn pll_minimum_output() {
+        // 8MHz / 2 * 43 / 16, a 172MHz VCO, is the nearest to 10.625MHz.
+        let config = PllConfig::for_target(8_000_000, PLL_MIN_OUTPUT_FREQ).unwrap();
+        assert_eq!(config.feedback_div(), 43);
+        assert_eq!(config.output_div(), 16);
+        assert!(config.lf_mode());
+        assert_eq!(config.output_freq(8_000_000), 10_750_000);
+    }
+
+    #[test]
+    fn pll_reference_out_of_range() {
+        assert_eq!(
+            PllConfig::for_target(3_000_000, 100_000_000),
+            Err(Error::PllReferenceFrequencyOutOfRange)
+        );
+        assert_eq!(
+            PllConfig::for_target(65_000_000, 100_000_000),
+            Err(Error::PllReferenceFrequencyOutOfRange)
+        );
+    }
+
+    #[test]
+    fn pll_target_out_of_range() {
+        assert_eq!(
+            PllConfig::for_target(8_000_000, 10_000_000),
+            Err(Error::PllTargetFrequencyOutOfRange)
+        );
+        assert_eq!(
+            PllConfig::for_target(8_000_000, 151_000_000),
+            Err(Error::PllTargetFrequencyOutOfRange)
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! startup.rs implements non-blocking startup of the FLL, PLL, ECO and
+//! WCO as nb-style state machines: FllStartup, PllStartup, EcoStartup
+//! and WcoStartup, which is in drivers::backup::wco as the WCO is in the
+//! backup domain. Each poll advances the startup as far as
+//! the hardware allows and returns nb::Error::WouldBlock while waiting,
+//! so the application can service the watchdog and IPC between polls.
+//! The timeouts count down by the elapsed_us passed to each poll, the
+//! time since the previous poll, and produce the same errors as the
+//! blocking start_fll, start_pll, start_eco and Backup::start_wco which
+//! are built on these state machines.
+//!```
+//! let mut fll_startup = FllStartup::new(200_000);
+//! loop {
+//!     match fll_startup.poll(&system, 10) {
+//!         Ok(()) => break,
+//!         Err(nb::Error::WouldBlock) => { system.wdt_clear(); delay.delay_us(10); }
+//!         Err(nb::Error::Other(e)) => return Err(e),
+//!     }
+//! }
+//!```
+
+#![deny(unsafe_code)]
+
+use cortex_m::asm::delay;
+
+use crate::drivers::system::System;
+use crate::error::Error;
+
+/// block_on polls a startup state machine to completion, polling every
+/// (approximately) microsecond as the blocking startup functions do.
+pub(crate) fn block_on<F>(mut poll: F) -> Result<(), Error>
+where
+    F: FnMut(u32) -> nb::Result<(), Error>,
+{
+    loop {
+        match poll(1) {
+            Ok(()) => return Ok(()),
+            Err(nb::Error::Other(e)) => return Err(e),
+            Err(nb::Error::WouldBlock) => delay(100),
+        }
+    }
+}
+
+/// Timeout counts down the time remaining for a startup step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Timeout {
+    remaining_us: u32,
+}
+
+impl Timeout {
+    #[inline(always)]
+    pub(crate) fn new(timeout_us: u32) -> Self {
+        Timeout {
+            remaining_us: timeout_us,
+        }
+    }
+    /// wait returns WouldBlock while time remains, otherwise error.
+    pub(crate) fn wait(&mut self, elapsed_us: u32, error: Error) -> nb::Result<(), Error> {
+        if self.remaining_us == 0 {
+            return Err(nb::Error::Other(error));
+        }
+        self.remaining_us = self.remaining_us.saturating_sub(elapsed_us);
+        Err(nb::Error::WouldBlock)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FllState {
+    Start,
+    CcoStarting,
+    Locking,
+    Done,
+}
+
+/// FllStartup starts a configured FLL, see System::configure_fll.
+/// trm 20.4.2.2 Enabling and Disabling the FLL:
+/// 1. Enable the CCO and wait for CCO_READY.
+/// 2. Select the FLL reference with BYPASS_SEL, enable the FLL and wait
+///    for LOCKED.
+/// 3. Select the FLL output with BYPASS_SEL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FllStartup {
+    state: FllState,
+    timeout: Timeout,
+}
+
+impl FllStartup {
+    pub fn new(timeout_us: u32) -> Self {
+        FllStartup {
+            state: FllState::Start,
+            timeout: Timeout::new(timeout_us),
+        }
+    }
+    pub fn poll(&mut self, system: &System, elapsed_us: u32) -> nb::Result<(), Error> {
+        let srss = &system.srss;
+        loop {
+            match self.state {
+                FllState::Start => {
+                    if srss.clk_fll_config.read().fll_enable().bit_is_set() {
+                        //Fll already started
+                        self.state = FllState::Done;
+                        continue;
+                    }
+                    if self.timeout.remaining_us == 0 {
+                        return Err(nb::Error::Other(
+                            Error::FllStartupCouldNotBeCompletedBeforeTimeout,
+                        ));
+                    }
+                    //start the cco stabilisation counter then enable the cco.
+                    srss.clk_trim_cco_ctl.modify(|_, w| w.enable_cnt().set_bit());
+                    srss.clk_fll_config4.modify(|_, w| w.cco_enable().set_bit());
+                    self.state = FllState::CcoStarting;
+                }
+                FllState::CcoStarting => {
+                    if srss.clk_fll_status.read().cco_ready().bit_is_clear() {
+                        return self
+                            .timeout
+                            .wait(elapsed_us, Error::FllStartupFailedCcoNotReady)
+                            .map_err(|e| self.fail(system, e));
+                    }
+                    srss.clk_fll_config3.modify(|_, w| w.bypass_sel().fll_ref());
+                    srss.clk_fll_config3.read().bypass_sel().is_fll_ref(); // read ensures write has completed.
+                    srss.clk_fll_config.modify(|_, w| w.fll_enable().set_bit());
+                    self.state = FllState::Locking;
+                }
+                FllState::Locking => {
+                    if srss.clk_fll_status.read().locked().bit_is_clear() {
+                        return self
+                            .timeout
+                            .wait(elapsed_us, Error::FllStartupFailedFllCouldNotBeLocked)
+                            .map_err(|e| self.fail(system, e));
+                    }
+                    // It takes seven reference clock cycles plus four FLL output
+                    // cycles to switch to the FLL output. Do not disable the FLL
+                    // before this time completes.
+                    srss.clk_fll_config3.modify(|_, w| w.bypass_sel().fll_out());
+                    delay(100);
+                    self.state = FllState::Done;
+                }
+                FllState::Done => return Ok(()),
+            }
+        }
+    }
+    /// fail leaves CLK_PATH0 on the FLL reference with the FLL and CCO
+    /// disabled when the startup times out.
+    fn fail(&self, system: &System, e: nb::Error<Error>) -> nb::Error<Error> {
+        if let nb::Error::Other(_) = e {
+            system.srss.clk_fll_config.modify(|_, w| w.fll_enable().clear_bit());
+            system.srss.clk_fll_config4.modify(|_, w| w.cco_enable().clear_bit());
+        }
+        e
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PllState {
+    Start,
+    Locking,
+    Done,
+}
+
+/// PllStartup starts a configured PLL, see System::configure_pll.
+/// CLK_PATH1 stays on the PLL reference until the PLL locks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PllStartup {
+    state: PllState,
+    timeout: Timeout,
+}
+
+impl PllStartup {
+    pub fn new(timeout_us: u32) -> Self {
+        PllStartup {
+            state: PllState::Start,
+            timeout: Timeout::new(timeout_us),
+        }
+    }
+    pub fn poll(&mut self, system: &System, elapsed_us: u32) -> nb::Result<(), Error> {
+        let srss = &system.srss;
+        loop {
+            match self.state {
+                PllState::Start => {
+                    if srss.clk_pll_config[0].read().enable().bit_is_set() {
+                        //Pll already started
+                        self.state = PllState::Done;
+                        continue;
+                    }
+                    if self.timeout.remaining_us == 0 {
+                        return Err(nb::Error::Other(
+                            Error::PllStartupCouldNotBeCompletedBeforeTimeout,
+                        ));
+                    }
+                    srss.clk_pll_config[0].modify(|_, w| w.bypass_sel().pll_ref());
+                    srss.clk_pll_config[0].modify(|_, w| w.enable().set_bit());
+                    self.state = PllState::Locking;
+                }
+                PllState::Locking => {
+                    if srss.clk_pll_status[0].read().locked().bit_is_clear() {
+                        return self
+                            .timeout
+                            .wait(elapsed_us, Error::PllStartupFailedPllCouldNotBeLocked)
+                            .map_err(|e| {
+                                if let nb::Error::Other(_) = e {
+                                    srss.clk_pll_config[0].modify(|_, w| w.enable().clear_bit());
+                                }
+                                e
+                            });
+                    }
+                    // Clear the sticky unlock flag set during lock acquisition.
+                    srss.clk_pll_status[0].modify(|_, w| w.unlock_occurred().set_bit());
+                    srss.clk_pll_config[0].modify(|_, w| w.bypass_sel().pll_out());
+                    self.state = PllState::Done;
+                }
+                PllState::Done => return Ok(()),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EcoState {
+    Start,
+    Starting,
+    Done,
+}
+
+/// EcoStartup starts a configured ECO, see System::configure_eco.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EcoStartup {
+    state: EcoState,
+    timeout: Timeout,
+}
+
+impl EcoStartup {
+    pub fn new(timeout_us: u32) -> Self {
+        EcoStartup {
+            state: EcoState::Start,
+            timeout: Timeout::new(timeout_us),
+        }
+    }
+    pub fn poll(&mut self, system: &System, elapsed_us: u32) -> nb::Result<(), Error> {
+        let srss = &system.srss;
+        loop {
+            match self.state {
+                EcoState::Start => {
+                    if srss.clk_eco_config.read().eco_en().bit_is_set() {
+                        //Eco already enabled, it may still be starting.
+                        self.state = EcoState::Starting;
+                        continue;
+                    }
+                    srss.clk_eco_config.modify(|_, w| w.eco_en().set_bit());
+                    self.state = EcoState::Starting;
+                }
+                EcoState::Starting => {
+                    if srss.clk_eco_status.read().eco_ready().bit_is_clear() {
+                        return self
+                            .timeout
+                            .wait(elapsed_us, Error::EcoStartupCouldNotBeCompletedBeforeTimeout)
+                            .map_err(|e| {
+                                if let nb::Error::Other(_) = e {
+                                    system.stop_eco();
+                                }
+                                e
+                            });
+                    }
+                    self.state = EcoState::Done;
+                }
+                EcoState::Done => return Ok(()),
+            }
+        }
+    }
+}
@@ -6,6 +6,7 @@
 
 //use embedded_hal::watchdog::{Watchdog, WatchdogEnable, WatchdogDisable};
 
+use crate::clocks::{Clocks, ILO_FREQ};
 use crate::drivers::system::System;
 use cortex_m::interrupt;
 
@@ -60,8 +61,22 @@ impl System {
     /// Any timeout_ms greater than 6.144seconds will be saturated to
     /// 6.144 seconds.
     /// The min timeout is 0.001 seconds.
+    /// The ILO is assumed to run at its nominal 32kHz, use
+    /// wdt_start_with_clocks when the LFCLK is known.
     //`/ A usage example is in examples/watchdog.rs
     pub fn wdt_start(&self, timeout_ms: u32) -> () {
+        self.wdt_start_lfclk(timeout_ms, ILO_FREQ);
+    }
+    /// wdt_start_with_clocks starts the watchdog using the LFCLK
+    /// frequency from the frozen Clocks to calculate the timeout.
+    #[inline(always)]
+    pub fn wdt_start_with_clocks(&self, timeout_ms: u32, clocks: &Clocks) -> () {
+        self.wdt_start_lfclk(timeout_ms, clocks.lf_clk());
+    }
+    /// wdt_start_lfclk starts the watchdog with a timeout calculated
+    /// from an LFCLK of lfclk_hz. The maximum timeout is 3 * 65536
+    /// LFCLK cycles, 6.144 seconds at 32kHz, longer timeouts saturate.
+    pub fn wdt_start_lfclk(&self, timeout_ms: u32, lfclk_hz: u32) -> () {
         //unlock the WDT_CTL
         interrupt::free(|_| {
             self.wdt_unlock();
@@ -75,18 +90,20 @@ impl System {
             //or time in secs = (2*2^(16-ignore_bits) + match_bits) / ilofreq
             //match_bits = time_in_secs * ilofreq - 2*2(16-ignore_bits)
             //or match_bits = time_in_msec * ilofreq / 1000.0 - 2*2(16-ignore_bits)
+            //ticks = time_in_msec * ilofreq / 1000.0, i.e. 32 per msec at 32kHz.
             let match_bits: u16;
             let ignore_bits: u8;
+            let ticks = timeout_ms as u64 * lfclk_hz as u64 / 1000;
 
-            if timeout_ms < 2049 {
+            if ticks < 65_568 {
                 ignore_bits = 12;
-                match_bits = (timeout_ms as f32 * 32.0 - 32.0) as u16; //32 = 2*2(16-12) (- (* 2049 32) 32)65536
-            } else if timeout_ms < 4096 {
+                match_bits = ticks.saturating_sub(32) as u16; //32 = 2*2(16-12) (- (* 2049 32) 32)65536
+            } else if ticks < 131_072 {
                 ignore_bits = 1;
-                match_bits = (timeout_ms as f32 * 32.0 - 65536.0) as u16; //65536 = 2*2(16-1) (- (* 4096 32) 65536)65536
-            } else if timeout_ms < 6144 {
+                match_bits = (ticks - 65_536) as u16; //65536 = 2*2(16-1) (- (* 4096 32) 65536)65536
+            } else if ticks < 196_608 {
                 ignore_bits = 0;
-                match_bits = (timeout_ms as f32 * 32.0 - 131072.0) as u16; //131072 = 2*2(16) (- (* 6144.0 32) 131072)65536.0
+                match_bits = (ticks - 131_072) as u16; //131072 = 2*2(16) (- (* 6144.0 32) 131072)65536.0
             } else {
                 ignore_bits = 0;
                 match_bits = 0xffff;
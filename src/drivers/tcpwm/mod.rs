@@ -0,0 +1,607 @@
+//! tcpwm/mod.rs implements the counters of the two TCPWM blocks, the
+//! general purpose timer, counter, PWM, quadrature decoder and capture
+//! peripherals, trm 18 Timer, Counter, and PWM:
+//! - TCPWM0: 8 32-bit counters -- Tcpwm0Counter0..Tcpwm0Counter7
+//! - TCPWM1: 24 16-bit counters -- Tcpwm1Counter0..Tcpwm1Counter23
+//! Each counter is an owned handle, split from its TCPWM block, with its
+//! own peripheral clock, see PeripheralClock::Tcpwm0Clocks0.., and its
+//! own interrupt, see InterruptSource::TCPWM_0_INTERRUPTS_0..
+//! The handles give register level access, the timer, PWM, quadrature
+//! and capture drivers are built on them.
+//!```
+//! let counters = p.TCPWM0.split();
+//! let mut counter = counters.counter0;
+//! counter.configure(Control { mode: CounterMode::Timer, ..Control::default() });
+//! counter.set_period(999);
+//! counter.enable();
+//! counter.start();
+//!```
+
+#![deny(unsafe_code)]
+
+use crate::drivers::cpuss::interrupt::InterruptSource;
+use crate::drivers::peri::PeripheralClock;
+use crate::pac::{TCPWM0, TCPWM1};
+
+/// Extension trait to split a TCPWM block into independent counters.
+pub trait TcpwmExt {
+    /// The parts to split the TCPWM into.
+    type Parts;
+
+    /// Splits the TCPWM block into independent counters.
+    fn split(self) -> Self::Parts;
+}
+
+/// CounterMode is the CTRL.MODE encoding of the counter functions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CounterMode {
+    Timer = 0,
+    Capture = 2,
+    Quadrature = 3,
+    Pwm = 4,
+    PwmDeadTime = 5,
+    PwmPseudoRandom = 6,
+}
+
+/// UpDownMode is the CTRL.UP_DOWN_MODE encoding of the count direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpDownMode {
+    /// Count up to PERIOD, terminal count on the reload to 0.
+    Up = 0,
+    /// Count down to 0, terminal count on the reload to PERIOD.
+    Down = 1,
+    /// Count up to PERIOD then down to 0, terminal count at 0.
+    UpDown0 = 2,
+    /// Count up to PERIOD then down to 0, terminal count at 0 and PERIOD.
+    UpDown1 = 3,
+}
+
+/// Prescaler divides the counter clock in the Timer, Capture and
+/// Quadrature modes, CTRL.GENERIC[2:0].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prescaler {
+    Div1 = 0,
+    Div2 = 1,
+    Div4 = 2,
+    Div8 = 3,
+    Div16 = 4,
+    Div32 = 5,
+    Div64 = 6,
+    Div128 = 7,
+}
+
+/// QuadratureMode is the CTRL.QUADRATURE_MODE encoding of the counts
+/// per encoder cycle, X1 counts one edge of phiA, X4 every edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuadratureMode {
+    X1 = 0,
+    X2 = 1,
+    X4 = 2,
+}
+
+/// Control holds the counter CTRL register fields.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Control {
+    pub mode: CounterMode,
+    pub up_down: UpDownMode,
+    /// one_shot stops the counter on a terminal count.
+    pub one_shot: bool,
+    /// generic is the prescaler or the dead time, depending on the mode.
+    pub generic: u8,
+    /// auto_reload_cc swaps CC and CC_BUFF on a terminal count.
+    pub auto_reload_cc: bool,
+    /// auto_reload_period swaps PERIOD and PERIOD_BUFF on a terminal count.
+    pub auto_reload_period: bool,
+    /// pwm_sync_kill keeps the PWM outputs killed until the next
+    /// terminal count, otherwise only while the kill input is active.
+    pub pwm_sync_kill: bool,
+    /// pwm_stop_on_kill stops the counter on a kill.
+    pub pwm_stop_on_kill: bool,
+    pub quadrature_mode: QuadratureMode,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Control {
+            mode: CounterMode::Timer,
+            up_down: UpDownMode::Up,
+            one_shot: false,
+            generic: Prescaler::Div1 as u8,
+            auto_reload_cc: false,
+            auto_reload_period: false,
+            pwm_sync_kill: false,
+            pwm_stop_on_kill: false,
+            quadrature_mode: QuadratureMode::X1,
+        }
+    }
+}
+
+/// TriggerInput is the TR_CTRL0 encoding of a counter input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerInput {
+    Zero,
+    One,
+    /// A trigger from the trigger multiplexer, 0..=13.
+    Trigger(u8),
+}
+
+impl TriggerInput {
+    #[inline(always)]
+    fn bits(self) -> u8 {
+        match self {
+            TriggerInput::Zero => 0,
+            TriggerInput::One => 1,
+            TriggerInput::Trigger(n) => n.min(13) + 2,
+        }
+    }
+}
+
+/// InputEdge is the TR_CTRL1 encoding of the edge detection of an input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEdge {
+    Rising = 0,
+    Falling = 1,
+    Both = 2,
+    /// Level passes the input through, e.g. the count input counts
+    /// every counter clock while it is high.
+    Level = 3,
+}
+
+/// LineMode is the TR_CTRL2 encoding of the action of a counter event
+/// on the PWM line output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineMode {
+    Set = 0,
+    Clear = 1,
+    Invert = 2,
+    NoChange = 3,
+}
+
+/// CounterEvent is a counter interrupt cause, the INTR register bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CounterEvent {
+    /// TerminalCount is the overflow, or underflow, of the counter.
+    TerminalCount = 0b01,
+    /// CompareMatch is a counter match with CC, or a capture.
+    CompareMatch = 0b10,
+}
+
+/// TcpwmCounter is implemented by every counter handle.
+pub trait TcpwmCounter {
+    /// GROUP is the TCPWM block, 0 or 1.
+    const GROUP: u8;
+    /// NUM is the counter number in its block.
+    const NUM: u8;
+    /// MAX is the largest COUNTER, CC and PERIOD value.
+    const MAX: u32;
+    /// CLOCK is the peripheral clock of the counter.
+    const CLOCK: PeripheralClock;
+    /// INTERRUPT is the interrupt source of the counter.
+    const INTERRUPT: InterruptSource;
+
+    /// configure writes CTRL, the counter must be disabled.
+    fn configure(&mut self, control: Control) -> ();
+    /// set_inputs selects the capture, count, reload, stop and start
+    /// inputs in TR_CTRL0.
+    fn set_inputs(
+        &mut self,
+        capture: TriggerInput,
+        count: TriggerInput,
+        reload: TriggerInput,
+        stop: TriggerInput,
+        start: TriggerInput,
+    ) -> ();
+    /// set_input_edges selects the edge detection of the capture, count,
+    /// reload, stop and start inputs in TR_CTRL1.
+    fn set_input_edges(
+        &mut self,
+        capture: InputEdge,
+        count: InputEdge,
+        reload: InputEdge,
+        stop: InputEdge,
+        start: InputEdge,
+    ) -> ();
+    /// set_line_modes selects the PWM line action on a compare match, an
+    /// overflow and an underflow in TR_CTRL2.
+    fn set_line_modes(&mut self, cc_match: LineMode, overflow: LineMode, underflow: LineMode) -> ();
+
+    fn counter(&self) -> u32;
+    fn set_counter(&mut self, value: u32) -> ();
+    fn cc(&self) -> u32;
+    fn set_cc(&mut self, value: u32) -> ();
+    fn cc_buff(&self) -> u32;
+    fn set_cc_buff(&mut self, value: u32) -> ();
+    fn period(&self) -> u32;
+    fn set_period(&mut self, value: u32) -> ();
+    fn period_buff(&self) -> u32;
+    fn set_period_buff(&mut self, value: u32) -> ();
+
+    /// enable enables the counter, it then waits for a start.
+    fn enable(&mut self) -> ();
+    /// disable disables the counter and resets it.
+    fn disable(&mut self) -> ();
+    /// start triggers the start command of the counter.
+    fn start(&mut self) -> ();
+    /// stop triggers the stop command of the counter.
+    fn stop(&mut self) -> ();
+    /// reload triggers the reload command of the counter.
+    fn reload(&mut self) -> ();
+    /// capture triggers the capture command of the counter, in the PWM
+    /// modes it switches CC/CC_BUFF and PERIOD/PERIOD_BUFF on the next
+    /// terminal count.
+    fn capture(&mut self) -> ();
+    /// is_running returns true while the counter is counting.
+    fn is_running(&self) -> bool;
+    /// is_counting_down returns true while the counter counts down.
+    fn is_counting_down(&self) -> bool;
+
+    /// is_pending returns true when an event is pending.
+    fn is_pending(&self, event: CounterEvent) -> bool;
+    /// clear_interrupt clears a pending event.
+    fn clear_interrupt(&mut self, event: CounterEvent) -> ();
+    /// listen forwards an event to the counter interrupt.
+    fn listen(&mut self, event: CounterEvent) -> ();
+    /// unlisten stops forwarding an event to the counter interrupt.
+    fn unlisten(&mut self, event: CounterEvent) -> ();
+}
+
+macro_rules! counters {
+    ($TCPWM:ident, $Counters:ident, $group:expr, $max:expr, $bits:ty, [
+     $($CNT:ident: ($cnt:ident, $num:expr, $CLOCK:ident, $INTERRUPT:ident)),+
+    ]) => {
+        /// TCPWM counters
+        pub struct $Counters {
+            $(
+                /// Counter
+                pub $cnt: $CNT,
+            )+
+        }
+
+        impl TcpwmExt for $TCPWM {
+            type Parts = $Counters;
+
+            fn split(self) -> $Counters {
+                $Counters {
+                    $(
+                        $cnt: $CNT { _private: () },
+                    )+
+                }
+            }
+        }
+
+        $(
+            /// Counter
+            pub struct $CNT {
+                _private: (),
+            }
+
+            impl TcpwmCounter for $CNT {
+                const GROUP: u8 = $group;
+                const NUM: u8 = $num;
+                const MAX: u32 = $max;
+                const CLOCK: PeripheralClock = PeripheralClock::$CLOCK;
+                const INTERRUPT: InterruptSource = InterruptSource::$INTERRUPT;
+
+                #[allow(unsafe_code)]
+                fn configure(&mut self, control: Control) -> () {
+                    //Safety: the counter registers belong to this handle and
+                    //        every field is written from an enum or a bool.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num].ctrl.write(|w| {
+                            w.mode()
+                                .bits(control.mode as u8)
+                                .up_down_mode()
+                                .bits(control.up_down as u8)
+                                .one_shot()
+                                .bit(control.one_shot)
+                                .generic()
+                                .bits(control.generic)
+                                .auto_reload_cc()
+                                .bit(control.auto_reload_cc)
+                                .auto_reload_period()
+                                .bit(control.auto_reload_period)
+                                .pwm_sync_kill()
+                                .bit(control.pwm_sync_kill)
+                                .pwm_stop_on_kill()
+                                .bit(control.pwm_stop_on_kill)
+                                .quadrature_mode()
+                                .bits(control.quadrature_mode as u8)
+                        })
+                    }
+                }
+                #[allow(unsafe_code)]
+                fn set_inputs(
+                    &mut self,
+                    capture: TriggerInput,
+                    count: TriggerInput,
+                    reload: TriggerInput,
+                    stop: TriggerInput,
+                    start: TriggerInput,
+                ) -> () {
+                    //Safety: each input select is 4 bits wide.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num].tr_ctrl0.write(|w| {
+                            w.capture_sel()
+                                .bits(capture.bits())
+                                .count_sel()
+                                .bits(count.bits())
+                                .reload_sel()
+                                .bits(reload.bits())
+                                .stop_sel()
+                                .bits(stop.bits())
+                                .start_sel()
+                                .bits(start.bits())
+                        })
+                    }
+                }
+                #[allow(unsafe_code)]
+                fn set_input_edges(
+                    &mut self,
+                    capture: InputEdge,
+                    count: InputEdge,
+                    reload: InputEdge,
+                    stop: InputEdge,
+                    start: InputEdge,
+                ) -> () {
+                    //Safety: each edge select is written from an enum.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num].tr_ctrl1.write(|w| {
+                            w.capture_edge()
+                                .bits(capture as u8)
+                                .count_edge()
+                                .bits(count as u8)
+                                .reload_edge()
+                                .bits(reload as u8)
+                                .stop_edge()
+                                .bits(stop as u8)
+                                .start_edge()
+                                .bits(start as u8)
+                        })
+                    }
+                }
+                #[allow(unsafe_code)]
+                fn set_line_modes(&mut self, cc_match: LineMode, overflow: LineMode, underflow: LineMode) -> () {
+                    //Safety: each line mode is written from an enum.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num].tr_ctrl2.write(|w| {
+                            w.cc_match_mode()
+                                .bits(cc_match as u8)
+                                .overflow_mode()
+                                .bits(overflow as u8)
+                                .underflow_mode()
+                                .bits(underflow as u8)
+                        })
+                    }
+                }
+
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn counter(&self) -> u32 {
+                    //Safety: read only access to this counter.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].counter.read().counter().bits() as u32 }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn set_counter(&mut self, value: u32) -> () {
+                    //Safety: value is truncated to the counter width.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num]
+                            .counter
+                            .write(|w| w.counter().bits((value & $max) as $bits))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn cc(&self) -> u32 {
+                    //Safety: read only access to this counter.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].cc.read().cc().bits() as u32 }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn set_cc(&mut self, value: u32) -> () {
+                    //Safety: value is truncated to the counter width.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num]
+                            .cc
+                            .write(|w| w.cc().bits((value & $max) as $bits))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn cc_buff(&self) -> u32 {
+                    //Safety: read only access to this counter.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].cc_buff.read().cc().bits() as u32 }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn set_cc_buff(&mut self, value: u32) -> () {
+                    //Safety: value is truncated to the counter width.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num]
+                            .cc_buff
+                            .write(|w| w.cc().bits((value & $max) as $bits))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn period(&self) -> u32 {
+                    //Safety: read only access to this counter.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].period.read().period().bits() as u32 }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn set_period(&mut self, value: u32) -> () {
+                    //Safety: value is truncated to the counter width.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num]
+                            .period
+                            .write(|w| w.period().bits((value & $max) as $bits))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn period_buff(&self) -> u32 {
+                    //Safety: read only access to this counter.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].period_buff.read().period().bits() as u32 }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn set_period_buff(&mut self, value: u32) -> () {
+                    //Safety: value is truncated to the counter width.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num]
+                            .period_buff
+                            .write(|w| w.period().bits((value & $max) as $bits))
+                    }
+                }
+
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn enable(&mut self) -> () {
+                    //Safety: CTRL_SET writes are atomic and only act on the
+                    //        bits set.
+                    unsafe {
+                        (*$TCPWM::PTR)
+                            .ctrl_set
+                            .write(|w| w.counter_enabled().bits(1 << $num))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn disable(&mut self) -> () {
+                    //Safety: CTRL_CLR writes are atomic and only act on the
+                    //        bits set.
+                    unsafe {
+                        (*$TCPWM::PTR)
+                            .ctrl_clr
+                            .write(|w| w.counter_enabled().bits(1 << $num))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn start(&mut self) -> () {
+                    //Safety: CMD_START writes only act on the bits set.
+                    unsafe {
+                        (*$TCPWM::PTR)
+                            .cmd_start
+                            .write(|w| w.counter_start().bits(1 << $num))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn stop(&mut self) -> () {
+                    //Safety: CMD_STOP writes only act on the bits set.
+                    unsafe {
+                        (*$TCPWM::PTR)
+                            .cmd_stop
+                            .write(|w| w.counter_stop().bits(1 << $num))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn reload(&mut self) -> () {
+                    //Safety: CMD_RELOAD writes only act on the bits set.
+                    unsafe {
+                        (*$TCPWM::PTR)
+                            .cmd_reload
+                            .write(|w| w.counter_reload().bits(1 << $num))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn capture(&mut self) -> () {
+                    //Safety: CMD_CAPTURE writes only act on the bits set.
+                    unsafe {
+                        (*$TCPWM::PTR)
+                            .cmd_capture
+                            .write(|w| w.counter_capture().bits(1 << $num))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn is_running(&self) -> bool {
+                    //Safety: read only access to this counter.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].status.read().running().bit_is_set() }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn is_counting_down(&self) -> bool {
+                    //Safety: read only access to this counter.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].status.read().down().bit_is_set() }
+                }
+
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn is_pending(&self, event: CounterEvent) -> bool {
+                    //Safety: read only access to this counter.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].intr.read().bits() & event as u32 != 0 }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn clear_interrupt(&mut self, event: CounterEvent) -> () {
+                    //Safety: INTR bits are cleared by writing 1, other bits
+                    //        are unaffected.
+                    unsafe { (*$TCPWM::PTR).cnt[$num].intr.write(|w| w.bits(event as u32)) }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn listen(&mut self, event: CounterEvent) -> () {
+                    //Safety: the mask register belongs to this counter.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num]
+                            .intr_mask
+                            .modify(|r, w| w.bits(r.bits() | event as u32))
+                    }
+                }
+                #[allow(unsafe_code)]
+                #[inline(always)]
+                fn unlisten(&mut self, event: CounterEvent) -> () {
+                    //Safety: the mask register belongs to this counter.
+                    unsafe {
+                        (*$TCPWM::PTR).cnt[$num]
+                            .intr_mask
+                            .modify(|r, w| w.bits(r.bits() & !(event as u32)))
+                    }
+                }
+            }
+        )+
+    };
+}
+
+counters!(TCPWM0, Counters0, 0, 0xffff_ffff, u32, [
+    Tcpwm0Counter0: (counter0, 0, Tcpwm0Clocks0, TCPWM_0_INTERRUPTS_0),
+    Tcpwm0Counter1: (counter1, 1, Tcpwm0Clocks1, TCPWM_0_INTERRUPTS_1),
+    Tcpwm0Counter2: (counter2, 2, Tcpwm0Clocks2, TCPWM_0_INTERRUPTS_2),
+    Tcpwm0Counter3: (counter3, 3, Tcpwm0Clocks3, TCPWM_0_INTERRUPTS_3),
+    Tcpwm0Counter4: (counter4, 4, Tcpwm0Clocks4, TCPWM_0_INTERRUPTS_4),
+    Tcpwm0Counter5: (counter5, 5, Tcpwm0Clocks5, TCPWM_0_INTERRUPTS_5),
+    Tcpwm0Counter6: (counter6, 6, Tcpwm0Clocks6, TCPWM_0_INTERRUPTS_6),
+    Tcpwm0Counter7: (counter7, 7, Tcpwm0Clocks7, TCPWM_0_INTERRUPTS_7)
+]);
+
+counters!(TCPWM1, Counters1, 1, 0xffff, u16, [
+    Tcpwm1Counter0: (counter0, 0, Tcpwm1Clocks0, TCPWM_1_INTERRUPTS_0),
+    Tcpwm1Counter1: (counter1, 1, Tcpwm1Clocks1, TCPWM_1_INTERRUPTS_1),
+    Tcpwm1Counter2: (counter2, 2, Tcpwm1Clocks2, TCPWM_1_INTERRUPTS_2),
+    Tcpwm1Counter3: (counter3, 3, Tcpwm1Clocks3, TCPWM_1_INTERRUPTS_3),
+    Tcpwm1Counter4: (counter4, 4, Tcpwm1Clocks4, TCPWM_1_INTERRUPTS_4),
+    Tcpwm1Counter5: (counter5, 5, Tcpwm1Clocks5, TCPWM_1_INTERRUPTS_5),
+    Tcpwm1Counter6: (counter6, 6, Tcpwm1Clocks6, TCPWM_1_INTERRUPTS_6),
+    Tcpwm1Counter7: (counter7, 7, Tcpwm1Clocks7, TCPWM_1_INTERRUPTS_7),
+    Tcpwm1Counter8: (counter8, 8, Tcpwm1Clocks8, TCPWM_1_INTERRUPTS_8),
+    Tcpwm1Counter9: (counter9, 9, Tcpwm1Clocks9, TCPWM_1_INTERRUPTS_9),
+    Tcpwm1Counter10: (counter10, 10, Tcpwm1Clocks10, TCPWM_1_INTERRUPTS_10),
+    Tcpwm1Counter11: (counter11, 11, Tcpwm1Clocks11, TCPWM_1_INTERRUPTS_11),
+    Tcpwm1Counter12: (counter12, 12, Tcpwm1Clocks12, TCPWM_1_INTERRUPTS_12),
+    Tcpwm1Counter13: (counter13, 13, Tcpwm1Clocks13, TCPWM_1_INTERRUPTS_13),
+    Tcpwm1Counter14: (counter14, 14, Tcpwm1Clocks14, TCPWM_1_INTERRUPTS_14),
+    Tcpwm1Counter15: (counter15, 15, Tcpwm1Clocks15, TCPWM_1_INTERRUPTS_15),
+    Tcpwm1Counter16: (counter16, 16, Tcpwm1Clocks16, TCPWM_1_INTERRUPTS_16),
+    Tcpwm1Counter17: (counter17, 17, Tcpwm1Clocks17, TCPWM_1_INTERRUPTS_17),
+    Tcpwm1Counter18: (counter18, 18, Tcpwm1Clocks18, TCPWM_1_INTERRUPTS_18),
+    Tcpwm1Counter19: (counter19, 19, Tcpwm1Clocks19, TCPWM_1_INTERRUPTS_19),
+    Tcpwm1Counter20: (counter20, 20, Tcpwm1Clocks20, TCPWM_1_INTERRUPTS_20),
+    Tcpwm1Counter21: (counter21, 21, Tcpwm1Clocks21, TCPWM_1_INTERRUPTS_21),
+    Tcpwm1Counter22: (counter22, 22, Tcpwm1Clocks22, TCPWM_1_INTERRUPTS_22),
+    Tcpwm1Counter23: (counter23, 23, Tcpwm1Clocks23, TCPWM_1_INTERRUPTS_23)
+]);
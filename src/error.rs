@@ -24,6 +24,51 @@ pub enum Error{
     FllStartupCouldNotBeCompletedBeforeTimeout,
     FllStartupFailedCcoNotReady,
     FllStartupFailedFllCouldNotBeLocked,
+    FllTargetFrequencyOutOfRange,
+    PllReferenceFrequencyOutOfRange,
+    PllTargetFrequencyOutOfRange,
+    PllCouldNotBeConfigured,
+    PllStartupCouldNotBeCompletedBeforeTimeout,
+    PllStartupFailedPllCouldNotBeLocked,
+    LockSupervisorInvalidSafePath,
+    EcoFrequencyOutOfRange,
+    EcoCouldNotBeConfigured,
+    EcoStartupCouldNotBeCompletedBeforeTimeout,
+    WcoStartupCouldNotBeCompletedBeforeTimeout,
+    ClockMeasurementBusy,
+    ClockMeasurementCountOutOfRange,
+    ClockMeasurementCouldNotBeCompletedBeforeTimeout,
+    ClockMeasurementReferenceNotRunning,
+    IloTrimOutOfTolerance,
+    PiloNotEnabled,
+    PiloTrimOutOfTolerance,
+    //clocks
+    ClockHf0NotConfigured,
+    ClockFrequencyExceedsSystemModeLimit,
+    ClockOutputFrequencyOutOfRange,
+    ExtClkFrequencyOutOfRange,
+    SystemFrequencyCannotBeSet,
+    ClockListenersFull,
+    ClockRestoreFailed,
+    //clock supervisors
+    CsvLimitsOutOfRange,
+    //systick
+    SystickReferenceNotAvailable,
+    SystickFrequencyOutOfRange,
+    //peri clock dividers
+    PeriDividerOutOfRange,
+    //tcpwm
+    TimerNotRunning,
+    PwmPeriodOutOfRange,
+    PwmDeadTimeWithPrescaler,
+    //mcwdt
+    McwdtTimeoutOutOfRange,
+    //monotonic
+    MonotonicFrequencyMismatch,
+    //rtc
+    RtcInvalidDateTime,
+    RtcInvalidAlarm,
+    RtcBusy,
     //GPIO
     AttemptingToSetUnknownInterrupt,
     // cpuss erros
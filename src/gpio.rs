@@ -38,6 +38,59 @@ pub struct Input<MODE> {
 pub struct Output<MODE> {
     _mode: PhantomData<MODE>,
 }
+
+/// Alternate function mode (type state), the pin is connected to a
+/// peripheral by the HSIOM.
+pub struct Alternate<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+/// Hsiom is the HSIOM PORT_SEL connection of a pin, trm 26.4.
+/// The peripheral on each ACT and DS connection is given per pin in
+/// the datasheet pinout tables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hsiom {
+    Gpio = 0,
+    GpioDsi = 1,
+    DsiDsi = 2,
+    DsiGpio = 3,
+    Amuxa = 4,
+    Amuxb = 5,
+    AmuxaDsi = 6,
+    AmuxbDsi = 7,
+    Act0 = 8,
+    Act1 = 9,
+    Act2 = 10,
+    Act3 = 11,
+    Act4 = 12,
+    Act5 = 13,
+    Act6 = 14,
+    Act7 = 15,
+    Act8 = 16,
+    Act9 = 17,
+    Act10 = 18,
+    Act11 = 19,
+    Act12 = 20,
+    Act13 = 21,
+    Act14 = 22,
+    Act15 = 23,
+    Ds0 = 24,
+    Ds1 = 25,
+    Ds2 = 26,
+    Ds3 = 27,
+    Ds4 = 28,
+    Ds5 = 29,
+    Ds6 = 30,
+    Ds7 = 31,
+}
+/// AlternateInput is implemented by pins connected to a peripheral
+/// input by into_alternate_input.
+pub trait AlternateInput {
+    /// is_high reads the pin level, the input buffer stays enabled while
+    /// the pin is connected to the peripheral.
+    fn is_high(&self) -> bool;
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum EdgeSelect {
     Disable = 0,
@@ -73,7 +126,7 @@ macro_rules! gpio {
         use core::convert::Infallible;
 
         use embedded_hal::digital::v2::{OutputPin, InputPin};
-        use psoc6_pac::GPIO;
+        use psoc6_pac::{GPIO, HSIOM};
 
         /// GPIO parts
         pub struct Parts {
@@ -170,6 +223,55 @@ macro_rules! gpio {
                     self.set_drive_mode(7);
                     $Pi_j { _mode: PhantomData }
                 }
+                /// Connects the pin to a peripheral output through the HSIOM
+                /// as a strong output.
+                pub fn into_alternate_strong_output(self, hsiom: Hsiom, _cs: &CriticalSection) -> $Pi_j<Alternate<Strong>> {
+                    self.set_to_output();
+                    self.set_drive_mode(6);
+                    self.set_hsiom(hsiom);
+                    $Pi_j { _mode: PhantomData }
+                }
+                /// Connects the pin to a peripheral input through the HSIOM
+                /// as a high impedance input.
+                pub fn into_alternate_input(self, hsiom: Hsiom, _cs: &CriticalSection) -> $Pi_j<Alternate<HighZ>> {
+                    self.set_to_input();
+                    self.set_drive_mode(0);
+                    self.set_hsiom(hsiom);
+                    $Pi_j { _mode: PhantomData }
+                }
+                /// Connects the pin to a peripheral through the HSIOM with
+                /// the input buffer enabled and a strong drive, for
+                /// peripherals that both drive and read the pin.
+                pub fn into_alternate_strong_input(self, hsiom: Hsiom, _cs: &CriticalSection) -> $Pi_j<Alternate<Strong>> {
+                    self.set_to_input();
+                    self.set_drive_mode(6);
+                    self.set_hsiom(hsiom);
+                    $Pi_j { _mode: PhantomData }
+                }
+
+                /// Set the HSIOM connection for the pin
+                #[inline(always)]
+                fn set_hsiom(&self, hsiom: Hsiom) {
+                    let sel = hsiom as u8;
+                    //Safety: PAC const pointer can always be safely dereferenced
+                    // and every Hsiom discriminant is a valid PORT_SEL value.
+                    unsafe {
+                        match $j {
+                            0..=3 => (*HSIOM::PTR).$prti.port_sel0.modify(|_, w| match $j {
+                                0 => w.io0_sel().bits(sel),
+                                1 => w.io1_sel().bits(sel),
+                                2 => w.io2_sel().bits(sel),
+                                _ => w.io3_sel().bits(sel),
+                            }),
+                            _ => (*HSIOM::PTR).$prti.port_sel1.modify(|_, w| match $j {
+                                4 => w.io4_sel().bits(sel),
+                                5 => w.io5_sel().bits(sel),
+                                6 => w.io6_sel().bits(sel),
+                                _ => w.io7_sel().bits(sel),
+                            }),
+                        }
+                    }
+                }
 
 
                 /// Set the drive mode for the pin
@@ -191,6 +293,7 @@ macro_rules! gpio {
                 }
                 #[inline(always)]
                 fn set_to_input(&self) {
+                    self.set_hsiom(Hsiom::Gpio);
                     unsafe{(*GPIO::PTR).$prti.cfg.modify(|_, w| {
                         match $j {
                             0 => w.in_en0().set_bit(),
@@ -207,6 +310,7 @@ macro_rules! gpio {
                 }
                 #[inline(always)]
                 fn set_to_output(&self) {
+                    self.set_hsiom(Hsiom::Gpio);
                     unsafe{(*GPIO::PTR).$prti.cfg.modify(|_, w| {
                         match $j {
                             0 => w.in_en0().clear_bit(),
@@ -348,6 +452,13 @@ macro_rules! gpio {
                 }
             }
 
+            impl AlternateInput for $Pi_j<Alternate<HighZ>> {
+                #[inline(always)]
+                fn is_high(&self) -> bool {
+                    unsafe { (*GPIO::PTR).$prti.in_.read().$inx().bit_is_set() }
+                }
+            }
+
             impl<MODE> OutputPin for $Pi_j<Output<MODE>> {
                 type Error = Infallible;
                 #[inline(always)]
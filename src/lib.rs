@@ -2,16 +2,24 @@
 //!
 //! NOTE: This crate is currently a WIP!
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(try_trait_v2)]
 
 pub use embedded_hal as ehal;
+pub use embedded_hal_1 as ehal1;
 pub use psoc6_pac as pac;
 
+pub mod capture;
 pub mod clocks;
 pub mod delay;
 pub mod drivers;
 pub mod gpio;
+pub mod monotonic;
 pub mod prelude;
 pub mod psoc;
+pub mod pwm;
+pub mod quadrature;
+pub mod timebase;
+pub mod timer;
+pub mod trigger;
 pub mod error;
@@ -0,0 +1,352 @@
+//! Monotonic time
+//!
+//! A monotonic is a free running counter extended to 64 bits in
+//! software with a compare interrupt for scheduling, the time source
+//! of RTIC and embassy:
+//! - TcpwmMonotonic runs a TCPWM counter, normally a 32-bit TCPWM0
+//!   counter, from a peripheral clock divider. The CC match schedules
+//!   and the terminal count extends the count.
+//! - McwdtMonotonic runs an MCWDT from LFCLK, it keeps counting in
+//!   DeepSleep. C2 is the 32-bit count, extended on each toggle of its
+//!   top bit, and a C0 match schedules.
+//! The overflow events must be handled, by on_interrupt from the counter
+//! interrupt, at least once per half range of the counter. So the
+//! counter interrupt stays enabled with an empty schedule.
+//! Each monotonic owns its counter, so the CM0+ and the CM4 each run
+//! their own instance with the interrupt routed to their core.
+//!
+//! The rtic-monotonic feature implements rtic_monotonic::Monotonic with
+//! fugit 64-bit instants at HZ. The embassy-time-driver feature adds
+//! embassy::TimeDriver.
+//!```
+//! // 1MHz ticks from CLK_PERI at 100MHz
+//! dividers.div16_0.set_divider(100)?;
+//! dividers.div16_0.enable();
+//! let mono: TcpwmMonotonic<_, 1_000_000> = TcpwmMonotonic::new(counters.counter0, &dividers.div16_0, Prescaler::Div1)?;
+//!```
+
+#[cfg(feature = "embassy-time-driver")]
+pub mod embassy;
+
+use cortex_m::interrupt::free;
+
+use crate::drivers::cpuss::interrupt::InterruptSource;
+use crate::drivers::peri::PeriClockDivider;
+use crate::drivers::system::mcwdt::{Mcwdt, McwdtCounter, McwdtMode};
+use crate::drivers::tcpwm::{
+    Control, CounterEvent, CounterMode, InputEdge, Prescaler, TcpwmCounter, TriggerInput,
+};
+use crate::error::Error;
+
+/// TickCounter is a free running counter extended to 64 bits with a
+/// compare event.
+pub trait TickCounter {
+    /// TICK_HZ is the counter clock rate.
+    const TICK_HZ: u32;
+    /// ticks returns the 64-bit count.
+    fn ticks(&self) -> u64;
+    /// set_compare sets the count raising the compare event. The event
+    /// may be raised early when the count is beyond the counter range,
+    /// the scheduler checks the time and sets the compare again.
+    fn set_compare(&mut self, ticks: u64) -> ();
+    /// listen_compare forwards the compare event to the interrupt.
+    fn listen_compare(&mut self) -> ();
+    fn unlisten_compare(&mut self) -> ();
+    fn compare_pending(&self) -> bool;
+    fn clear_compare(&mut self) -> ();
+    /// on_interrupt handles the overflow events, it must be called from
+    /// the counter interrupt.
+    fn on_interrupt(&mut self) -> ();
+    /// interrupt returns the interrupt source of the counter.
+    fn interrupt(&self) -> InterruptSource;
+    /// reset restarts the count from 0.
+    fn reset(&mut self) -> ();
+}
+
+/// TcpwmMonotonic is a TCPWM counter in the timer mode counting HZ.
+pub struct TcpwmMonotonic<C: TcpwmCounter, const HZ: u32> {
+    counter: C,
+    overflows: u64,
+}
+
+impl<C: TcpwmCounter, const HZ: u32> TcpwmMonotonic<C, HZ> {
+    const RANGE: u64 = C::MAX as u64 + 1;
+
+    /// new connects the divider to the counter and starts counting from
+    /// 0 with the overflow interrupt forwarded. The divider must be set
+    /// and enabled.
+    /// It returns an error when the divider frequency divided by the
+    /// prescaler is not HZ.
+    pub fn new<D: PeriClockDivider>(
+        mut counter: C,
+        divider: &D,
+        prescaler: Prescaler,
+    ) -> Result<Self, Error> {
+        if divider.frequency() >> (prescaler as u32) != HZ {
+            return Err(Error::MonotonicFrequencyMismatch);
+        }
+        divider.connect(C::CLOCK);
+        counter.disable();
+        counter.configure(Control {
+            mode: CounterMode::Timer,
+            generic: prescaler as u8,
+            ..Control::default()
+        });
+        // Count every counter clock, no trigger inputs.
+        counter.set_inputs(
+            TriggerInput::Zero,
+            TriggerInput::One,
+            TriggerInput::Zero,
+            TriggerInput::Zero,
+            TriggerInput::Zero,
+        );
+        counter.set_input_edges(
+            InputEdge::Rising,
+            InputEdge::Level,
+            InputEdge::Rising,
+            InputEdge::Rising,
+            InputEdge::Rising,
+        );
+        counter.set_period(C::MAX);
+        counter.unlisten(CounterEvent::CompareMatch);
+        counter.listen(CounterEvent::TerminalCount);
+        let mut monotonic = TcpwmMonotonic {
+            counter,
+            overflows: 0,
+        };
+        TickCounter::reset(&mut monotonic);
+        Ok(monotonic)
+    }
+    /// free stops the counter and returns it.
+    pub fn free(mut self) -> C {
+        self.counter.disable();
+        self.counter
+    }
+}
+
+impl<C: TcpwmCounter, const HZ: u32> TickCounter for TcpwmMonotonic<C, HZ> {
+    const TICK_HZ: u32 = HZ;
+
+    fn ticks(&self) -> u64 {
+        free(|_| {
+            let count = self.counter.counter();
+            match self.counter.is_pending(CounterEvent::TerminalCount) {
+                // An unhandled overflow, the count read again is after
+                // it unless the counter is still at PERIOD.
+                true => {
+                    let count = self.counter.counter();
+                    let overflows = match count <= C::MAX / 2 {
+                        true => self.overflows + 1,
+                        false => self.overflows,
+                    };
+                    overflows * Self::RANGE + count as u64
+                }
+                false => self.overflows * Self::RANGE + count as u64,
+            }
+        })
+    }
+    #[inline(always)]
+    fn set_compare(&mut self, ticks: u64) -> () {
+        self.counter.set_cc((ticks % Self::RANGE) as u32);
+    }
+    #[inline(always)]
+    fn listen_compare(&mut self) -> () {
+        self.counter.listen(CounterEvent::CompareMatch);
+    }
+    #[inline(always)]
+    fn unlisten_compare(&mut self) -> () {
+        self.counter.unlisten(CounterEvent::CompareMatch);
+    }
+    #[inline(always)]
+    fn compare_pending(&self) -> bool {
+        self.counter.is_pending(CounterEvent::CompareMatch)
+    }
+    #[inline(always)]
+    fn clear_compare(&mut self) -> () {
+        self.counter.clear_interrupt(CounterEvent::CompareMatch);
+    }
+    fn on_interrupt(&mut self) -> () {
+        free(|_| {
+            if self.counter.is_pending(CounterEvent::TerminalCount) {
+                self.counter.clear_interrupt(CounterEvent::TerminalCount);
+                self.overflows += 1;
+            }
+        });
+    }
+    #[inline(always)]
+    fn interrupt(&self) -> InterruptSource {
+        C::INTERRUPT
+    }
+    fn reset(&mut self) -> () {
+        // Disabling resets the counter and any pending command.
+        self.counter.disable();
+        self.counter.set_counter(0);
+        self.counter.clear_interrupt(CounterEvent::TerminalCount);
+        self.counter.clear_interrupt(CounterEvent::CompareMatch);
+        self.overflows = 0;
+        self.counter.enable();
+        self.counter.start();
+    }
+}
+
+/// McwdtMonotonic is an MCWDT counting LFCLK cycles at HZ.
+pub struct McwdtMonotonic<const HZ: u32> {
+    mcwdt: Mcwdt,
+    // Toggles of the top bit of C2, two per C2 overflow.
+    half_periods: u64,
+}
+
+impl<const HZ: u32> McwdtMonotonic<HZ> {
+    /// MIN_DELAY is the shortest compare delay in LFCLK cycles, the C0
+    /// match takes effect after up to three cycles.
+    pub const MIN_DELAY: u16 = 4;
+
+    /// new starts C2 and C0 from 0 with the C2 interrupt forwarded.
+    /// It returns an error when lfclk_hz is not HZ.
+    pub fn new(mut mcwdt: Mcwdt, lfclk_hz: u32) -> Result<Self, Error> {
+        if lfclk_hz != HZ {
+            return Err(Error::MonotonicFrequencyMismatch);
+        }
+        mcwdt.disable(McwdtCounter::C0);
+        mcwdt.disable(McwdtCounter::C2);
+        mcwdt.configure_c0(McwdtMode::Interrupt, 0, false);
+        mcwdt.configure_c2(true, 31, false);
+        mcwdt.unlisten(McwdtCounter::C0);
+        mcwdt.listen(McwdtCounter::C2);
+        let mut monotonic = McwdtMonotonic {
+            mcwdt,
+            half_periods: 0,
+        };
+        TickCounter::reset(&mut monotonic);
+        monotonic.mcwdt.enable(McwdtCounter::C0);
+        monotonic.mcwdt.enable(McwdtCounter::C2);
+        Ok(monotonic)
+    }
+    /// free stops the counters and returns the MCWDT.
+    pub fn free(mut self) -> Mcwdt {
+        self.mcwdt.unlisten(McwdtCounter::C0);
+        self.mcwdt.unlisten(McwdtCounter::C2);
+        self.mcwdt.disable(McwdtCounter::C0);
+        self.mcwdt.disable(McwdtCounter::C2);
+        self.mcwdt
+    }
+}
+
+impl<const HZ: u32> TickCounter for McwdtMonotonic<HZ> {
+    const TICK_HZ: u32 = HZ;
+
+    fn ticks(&self) -> u64 {
+        free(|_| {
+            let half_periods = self.half_periods;
+            // The parity of the half periods is the expected top bit of
+            // C2, a C2 toggle not yet handled then still counts.
+            let count = self.mcwdt.c2() ^ (((half_periods & 1) as u32) << 31);
+            (half_periods << 31) + count as u64
+        })
+    }
+    /// set_compare sets the C0 match, a delay beyond the C0 range
+    /// raises the event after 65535 cycles to check again.
+    fn set_compare(&mut self, ticks: u64) -> () {
+        let delay = ticks
+            .saturating_sub(self.ticks())
+            .max(Self::MIN_DELAY as u64)
+            .min(u16::MAX as u64) as u16;
+        let match_value = self.mcwdt.c0().wrapping_add(delay);
+        self.mcwdt.set_c0_match(match_value);
+    }
+    #[inline(always)]
+    fn listen_compare(&mut self) -> () {
+        self.mcwdt.listen(McwdtCounter::C0);
+    }
+    #[inline(always)]
+    fn unlisten_compare(&mut self) -> () {
+        self.mcwdt.unlisten(McwdtCounter::C0);
+    }
+    #[inline(always)]
+    fn compare_pending(&self) -> bool {
+        self.mcwdt.is_pending(McwdtCounter::C0)
+    }
+    #[inline(always)]
+    fn clear_compare(&mut self) -> () {
+        self.mcwdt.clear_interrupt(McwdtCounter::C0);
+    }
+    fn on_interrupt(&mut self) -> () {
+        free(|_| {
+            if self.mcwdt.is_pending(McwdtCounter::C2) {
+                self.mcwdt.clear_interrupt(McwdtCounter::C2);
+                self.half_periods += 1;
+            }
+        });
+    }
+    #[inline(always)]
+    fn interrupt(&self) -> InterruptSource {
+        self.mcwdt.interrupt()
+    }
+    fn reset(&mut self) -> () {
+        self.mcwdt.reset_counter(McwdtCounter::C0);
+        self.mcwdt.reset_counter(McwdtCounter::C2);
+        self.mcwdt.clear_interrupt(McwdtCounter::C0);
+        self.mcwdt.clear_interrupt(McwdtCounter::C2);
+        self.half_periods = 0;
+    }
+}
+
+#[cfg(feature = "rtic-monotonic")]
+mod rtic {
+    use super::{McwdtMonotonic, TcpwmMonotonic, TickCounter};
+    use crate::drivers::tcpwm::TcpwmCounter;
+    use fugit::{TimerDurationU64, TimerInstantU64};
+    use rtic_monotonic::Monotonic;
+
+    macro_rules! monotonic {
+        ($([$($generics:tt)*] $Mono:ty),+) => {
+            $(
+                impl<$($generics)*> Monotonic for $Mono {
+                    // The overflow events need the interrupt.
+                    const DISABLE_INTERRUPT_ON_EMPTY_QUEUE: bool = false;
+
+                    type Instant = TimerInstantU64<HZ>;
+                    type Duration = TimerDurationU64<HZ>;
+
+                    #[inline(always)]
+                    fn now(&mut self) -> Self::Instant {
+                        Self::Instant::from_ticks(self.ticks())
+                    }
+                    #[inline(always)]
+                    fn set_compare(&mut self, instant: Self::Instant) {
+                        TickCounter::set_compare(self, instant.ticks());
+                    }
+                    #[inline(always)]
+                    fn clear_compare_flag(&mut self) {
+                        self.clear_compare();
+                    }
+                    #[inline(always)]
+                    fn zero() -> Self::Instant {
+                        Self::Instant::from_ticks(0)
+                    }
+                    #[allow(unsafe_code)]
+                    unsafe fn reset(&mut self) {
+                        TickCounter::reset(self);
+                    }
+                    #[inline(always)]
+                    fn on_interrupt(&mut self) {
+                        TickCounter::on_interrupt(self);
+                    }
+                    #[inline(always)]
+                    fn enable_timer(&mut self) {
+                        self.listen_compare();
+                    }
+                    #[inline(always)]
+                    fn disable_timer(&mut self) {
+                        self.unlisten_compare();
+                    }
+                }
+            )+
+        };
+    }
+
+    monotonic!(
+        [C: TcpwmCounter, const HZ: u32] TcpwmMonotonic<C, HZ>,
+        [const HZ: u32] McwdtMonotonic<HZ>
+    );
+}
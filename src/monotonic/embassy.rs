@@ -0,0 +1,137 @@
+//! embassy.rs implements the embassy-time driver on a monotonic.
+//! TimeDriver is registered as the global driver, the monotonic passed
+//! to init must count at the embassy-time tick rate and the counter
+//! interrupt must call on_interrupt. There is one alarm, enough for the
+//! embassy executor.
+//!```
+//! embassy_time_driver::time_driver_impl!(
+//!     static DRIVER: TimeDriver<McwdtMonotonic<32_768>> = TimeDriver::new()
+//! );
+//! DRIVER.init(McwdtMonotonic::new(mcwdt0, clocks.lf_clk())?)?;
+//! // in the MCWDT interrupt
+//! DRIVER.on_interrupt();
+//!```
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::{free, Mutex};
+use embassy_time_driver::{AlarmHandle, Driver, TICK_HZ};
+
+use crate::error::Error;
+use crate::monotonic::TickCounter;
+
+/// TimeDriver is the embassy-time driver of a monotonic.
+pub struct TimeDriver<T: TickCounter> {
+    monotonic: Mutex<RefCell<Option<T>>>,
+    allocated: Mutex<Cell<bool>>,
+    // Alarm time, u64::MAX when not set.
+    timestamp: Mutex<Cell<u64>>,
+    callback: Mutex<Cell<Option<fn(*mut ())>>>,
+    // The callback context pointer.
+    context: Mutex<Cell<usize>>,
+}
+
+impl<T: TickCounter> TimeDriver<T> {
+    /// new returns a driver without a monotonic, the time is 0 until
+    /// init.
+    pub const fn new() -> Self {
+        TimeDriver {
+            monotonic: Mutex::new(RefCell::new(None)),
+            allocated: Mutex::new(Cell::new(false)),
+            timestamp: Mutex::new(Cell::new(u64::MAX)),
+            callback: Mutex::new(Cell::new(None)),
+            context: Mutex::new(Cell::new(0)),
+        }
+    }
+    /// init sets the monotonic of the driver.
+    /// It returns an error when the monotonic does not count at the
+    /// embassy-time tick rate.
+    pub fn init(&self, monotonic: T) -> Result<(), Error> {
+        if T::TICK_HZ as u64 != TICK_HZ {
+            return Err(Error::MonotonicFrequencyMismatch);
+        }
+        free(|cs| self.monotonic.borrow(cs).replace(Some(monotonic)));
+        Ok(())
+    }
+    /// on_interrupt handles the monotonic events and calls the alarm
+    /// callback when the alarm time is reached, it must be called from
+    /// the counter interrupt.
+    pub fn on_interrupt(&self) -> () {
+        let callback = free(|cs| {
+            let mut monotonic = self.monotonic.borrow(cs).borrow_mut();
+            let monotonic = monotonic.as_mut()?;
+            monotonic.on_interrupt();
+            if !monotonic.compare_pending() {
+                return None;
+            }
+            monotonic.clear_compare();
+            let timestamp = self.timestamp.borrow(cs).get();
+            if timestamp > monotonic.ticks() {
+                // Early, beyond the counter range.
+                monotonic.set_compare(timestamp);
+                return None;
+            }
+            self.timestamp.borrow(cs).set(u64::MAX);
+            monotonic.unlisten_compare();
+            let context = self.context.borrow(cs).get();
+            self.callback.borrow(cs).get().map(|f| (f, context))
+        });
+        if let Some((f, context)) = callback {
+            f(context as *mut ());
+        }
+    }
+}
+
+impl<T: TickCounter + Send + 'static> Driver for TimeDriver<T> {
+    fn now(&self) -> u64 {
+        free(|cs| {
+            self.monotonic
+                .borrow(cs)
+                .borrow()
+                .as_ref()
+                .map_or(0, |monotonic| monotonic.ticks())
+        })
+    }
+    #[allow(unsafe_code)]
+    unsafe fn allocate_alarm(&self) -> Option<AlarmHandle> {
+        free(|cs| {
+            let allocated = self.allocated.borrow(cs);
+            match allocated.get() {
+                true => None,
+                false => {
+                    allocated.set(true);
+                    //Safety: 0 is the only alarm and allocated once.
+                    Some(AlarmHandle::new(0))
+                }
+            }
+        })
+    }
+    fn set_alarm_callback(&self, _alarm: AlarmHandle, callback: fn(*mut ()), ctx: *mut ()) {
+        free(|cs| {
+            self.callback.borrow(cs).set(Some(callback));
+            self.context.borrow(cs).set(ctx as usize);
+        });
+    }
+    /// set_alarm returns false when the time has already passed, the
+    /// callback is then not called.
+    fn set_alarm(&self, _alarm: AlarmHandle, timestamp: u64) -> bool {
+        free(|cs| {
+            let mut monotonic = self.monotonic.borrow(cs).borrow_mut();
+            let monotonic = match monotonic.as_mut() {
+                Some(monotonic) => monotonic,
+                None => return false,
+            };
+            self.timestamp.borrow(cs).set(timestamp);
+            monotonic.set_compare(timestamp);
+            monotonic.listen_compare();
+            // Checked after setting the compare, so a time reached since
+            // is either seen here or raises the compare event.
+            if timestamp <= monotonic.ticks() {
+                self.timestamp.borrow(cs).set(u64::MAX);
+                monotonic.unlisten_compare();
+                return false;
+            }
+            true
+        })
+    }
+}
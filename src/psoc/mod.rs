@@ -6,7 +6,8 @@ use crate::pac::Peripherals;
 use crate::drivers::{
    // backup::Backup,
     cpuss::Cpuss,
-    //flashc::Flash,
+    flashc::Flash,
+    peri::{DividerExt, Dividers},
     ipc::{
     //    semaphore::Semaphore,
         Channels,
@@ -14,11 +15,12 @@ use crate::drivers::{
         IpcChannel
     },
     // prot::Prot,
-    //system::System,
+    system::System,
 };
 
 
 pub mod system_channels;
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SystemMode {
     Lp,
     Ulp,
@@ -41,12 +43,13 @@ pub struct Modes {
 // }
 
 pub struct Psoc {
-    // pub system: System,
+    pub system: System,
     pub cpuss: Cpuss,
-    // pub flash: Flash,
+    pub flash: Flash,
     pub ipc: Channels,
     pub ipc_intr: IntrStructs,
     pub gpio: Parts,
+    pub dividers: Dividers,
     // pub prot: Prot,
     // pub modes: Modes,
     // pub backup: Backup,
@@ -58,13 +61,15 @@ impl Psoc {
         let p = Peripherals::take().unwrap();
         let gpio = p.GPIO.split();
         let (ipc, ipc_intr) = p.IPC.split();
+        let dividers = p.PERI.split();
         Psoc {
-            // system: System::from(p.SRSS),
+            system: System::from(p.SRSS),
             cpuss: Cpuss::from(p.CPUSS),
-            // flash: Flash::from(p.FLASHC),
+            flash: Flash::from(p.FLASHC),
             ipc,
             ipc_intr,
             gpio,
+            dividers,
             // prot: Prot::from(p.PROT),
             // modes: Modes {
             //     cm0p: CpuMode::Active,
@@ -0,0 +1,517 @@
+//! Pulse width modulation
+//!
+//! Pwm runs a TCPWM counter in the PWM mode driving its line output and,
+//! optionally, the complementary line_n output. The period and duty are
+//! set in counter ticks, or the duty as a fraction of the period:
+//! - Edge alignment counts up, the line is high from the start of the
+//!   period for duty ticks.
+//! - Center alignment counts up and down, the line is high for duty
+//!   ticks centered on the middle of the period.
+//! With a dead time the counter runs in the PWM_DT mode, inserting the
+//! dead time before each rising edge of line and line_n, the prescaler
+//! is then not available.
+//! Period and duty changes are written to the buffer registers and take
+//! effect on the next terminal count so every period is complete.
+//! A kill input, from the trigger multiplexer, forces both outputs low,
+//! see KillMode.
+//!```
+//! let counters = p.TCPWM0.split();
+//! let config = PwmConfig::new(1_000).alignment(Alignment::Center).dead_time(10);
+//! let mut pwm = cortex_m::interrupt::free(|cs| {
+//!     Pwm::with_complementary(counters.counter0, &dividers.div16_0, gpio.p0_0, gpio.p0_1, config, cs)
+//! })?;
+//! pwm.set_duty_fraction(1, 4);
+//! pwm.enable();
+//!```
+//! The line pins of each counter, from the datasheet pinout tables, are
+//! connected by the LinePin and LineComplPin conversions, ACT_8 for
+//! TCPWM0 and ACT_9 for TCPWM1.
+
+use core::convert::Infallible;
+
+use crate::drivers::cpuss::interrupt::InterruptSource;
+use crate::drivers::peri::PeriClockDivider;
+use crate::drivers::tcpwm::{
+    Control, CounterEvent, CounterMode, InputEdge, LineMode, Prescaler, TcpwmCounter,
+    TriggerInput, UpDownMode,
+};
+use crate::drivers::tcpwm::{
+    Tcpwm0Counter0, Tcpwm0Counter1, Tcpwm0Counter2, Tcpwm0Counter3, Tcpwm0Counter4,
+    Tcpwm0Counter5, Tcpwm0Counter6, Tcpwm0Counter7, Tcpwm1Counter0, Tcpwm1Counter1,
+    Tcpwm1Counter10, Tcpwm1Counter11, Tcpwm1Counter12, Tcpwm1Counter13, Tcpwm1Counter14,
+    Tcpwm1Counter15, Tcpwm1Counter16, Tcpwm1Counter17, Tcpwm1Counter18, Tcpwm1Counter19,
+    Tcpwm1Counter2, Tcpwm1Counter20, Tcpwm1Counter21, Tcpwm1Counter22, Tcpwm1Counter23,
+    Tcpwm1Counter3, Tcpwm1Counter4, Tcpwm1Counter5, Tcpwm1Counter6, Tcpwm1Counter7,
+    Tcpwm1Counter8, Tcpwm1Counter9,
+};
+use crate::ehal;
+use crate::ehal1;
+use crate::error::Error;
+use crate::gpio::{self, Alternate, Hsiom, Strong};
+use cortex_m::interrupt::CriticalSection;
+
+/// LinePin is implemented by the pins that can carry the line output of
+/// the counter C.
+pub trait LinePin<C> {
+    type Output;
+    /// into_line connects the pin to the line output.
+    fn into_line(self, cs: &CriticalSection) -> Self::Output;
+}
+
+/// LineComplPin is implemented by the pins that can carry the
+/// complementary line_n output of the counter C.
+pub trait LineComplPin<C> {
+    type Output;
+    /// into_line_compl connects the pin to the line_n output.
+    fn into_line_compl(self, cs: &CriticalSection) -> Self::Output;
+}
+
+/// NoPin is used when the line_n output is not connected.
+pub struct NoPin;
+
+impl<C> LineComplPin<C> for NoPin {
+    type Output = NoPin;
+    #[inline(always)]
+    fn into_line_compl(self, _cs: &CriticalSection) -> NoPin {
+        self
+    }
+}
+
+/// Alignment of the pulse in the period.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alignment {
+    Edge,
+    Center,
+}
+
+/// KillMode selects the effect of the kill input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KillMode {
+    /// Async kills the outputs while the kill input is high.
+    Async,
+    /// Sync kills the outputs from a kill edge to the next terminal count.
+    Sync,
+    /// Stop kills the outputs and stops the counter until restarted by
+    /// Pwm::enable.
+    Stop,
+}
+
+/// PwmConfig holds the PWM settings applied by Pwm::new.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PwmConfig {
+    period: u32,
+    alignment: Alignment,
+    prescaler: Prescaler,
+    dead_time: u8,
+    kill: Option<(TriggerInput, KillMode)>,
+}
+
+impl PwmConfig {
+    /// new returns an edge aligned configuration with a period of
+    /// period_ticks counter clocks, no prescaler, dead time or kill input.
+    pub fn new(period_ticks: u32) -> Self {
+        PwmConfig {
+            period: period_ticks,
+            alignment: Alignment::Edge,
+            prescaler: Prescaler::Div1,
+            dead_time: 0,
+            kill: None,
+        }
+    }
+    pub fn alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+    /// prescaler divides the counter clock, it cannot be combined with
+    /// a dead time.
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+    /// dead_time sets the delay of the line and line_n rising edges in
+    /// counter clocks, 0..=255.
+    pub fn dead_time(mut self, dead_time_ticks: u8) -> Self {
+        self.dead_time = dead_time_ticks;
+        self
+    }
+    /// kill selects the kill input.
+    pub fn kill(mut self, input: TriggerInput, mode: KillMode) -> Self {
+        self.kill = Some((input, mode));
+        self
+    }
+}
+
+/// Pwm is a TCPWM counter in the PWM mode owning its output pins.
+pub struct Pwm<C: TcpwmCounter, L: LinePin<C>, N: LineComplPin<C> = NoPin> {
+    counter: C,
+    line: L::Output,
+    line_compl: N::Output,
+    alignment: Alignment,
+    tick_hz: u32,
+    period: u32,
+    duty: u32,
+}
+
+impl<C: TcpwmCounter, L: LinePin<C>> Pwm<C, L, NoPin> {
+    /// new configures a PWM on the line output only, see
+    /// Pwm::with_complementary.
+    pub fn new<D: PeriClockDivider>(
+        counter: C,
+        divider: &D,
+        line: L,
+        config: PwmConfig,
+        cs: &CriticalSection,
+    ) -> Result<Self, Error> {
+        Pwm::with_complementary(counter, divider, line, NoPin, config, cs)
+    }
+}
+
+impl<C: TcpwmCounter, L: LinePin<C>, N: LineComplPin<C>> Pwm<C, L, N> {
+    /// with_complementary connects the divider to the counter and the
+    /// pins to its outputs and configures a stopped PWM with a duty of 0.
+    /// The divider must be set and enabled.
+    /// It returns an error when the period is out of range for the
+    /// counter or a dead time is combined with a prescaler.
+    pub fn with_complementary<D: PeriClockDivider>(
+        mut counter: C,
+        divider: &D,
+        line: L,
+        line_compl: N,
+        config: PwmConfig,
+        cs: &CriticalSection,
+    ) -> Result<Self, Error> {
+        let period_reg = period_register(config.alignment, config.period, C::MAX)?;
+        let (mode, generic) = match config.dead_time {
+            0 => (CounterMode::Pwm, config.prescaler as u8),
+            dead_time => match config.prescaler {
+                Prescaler::Div1 => (CounterMode::PwmDeadTime, dead_time),
+                _ => return Err(Error::PwmDeadTimeWithPrescaler),
+            },
+        };
+        let (kill_input, kill_edge, kill_mode) = match config.kill {
+            // An asynchronous kill follows the level of the input.
+            Some((input, KillMode::Async)) => (input, InputEdge::Level, KillMode::Async),
+            Some((input, mode)) => (input, InputEdge::Rising, mode),
+            None => (TriggerInput::Zero, InputEdge::Rising, KillMode::Async),
+        };
+
+        divider.connect(C::CLOCK);
+        counter.disable();
+        counter.configure(Control {
+            mode,
+            // Center aligned the terminal count, and the buffer
+            // exchange, is only at 0 so both halves of a period match.
+            up_down: match config.alignment {
+                Alignment::Edge => UpDownMode::Up,
+                Alignment::Center => UpDownMode::UpDown0,
+            },
+            one_shot: false,
+            generic,
+            auto_reload_cc: true,
+            auto_reload_period: true,
+            pwm_sync_kill: kill_mode == KillMode::Sync,
+            pwm_stop_on_kill: kill_mode == KillMode::Stop,
+            ..Control::default()
+        });
+        // The capture input is the period/duty switch, triggered by
+        // software. The stop input is the kill input.
+        counter.set_inputs(
+            TriggerInput::Zero,
+            TriggerInput::One,
+            TriggerInput::Zero,
+            kill_input,
+            TriggerInput::Zero,
+        );
+        counter.set_input_edges(
+            InputEdge::Rising,
+            InputEdge::Level,
+            InputEdge::Rising,
+            kill_edge,
+            InputEdge::Rising,
+        );
+        match config.alignment {
+            Alignment::Edge => counter.set_line_modes(LineMode::Clear, LineMode::Set, LineMode::NoChange),
+            Alignment::Center => counter.set_line_modes(LineMode::Invert, LineMode::Set, LineMode::Clear),
+        }
+        let mut pwm = Pwm {
+            counter,
+            line: line.into_line(cs),
+            line_compl: line_compl.into_line_compl(cs),
+            alignment: config.alignment,
+            tick_hz: divider.frequency() >> (config.prescaler as u32),
+            period: config.period,
+            duty: 0,
+        };
+        let cc = pwm.cc_for(0);
+        pwm.counter.set_counter(0);
+        pwm.counter.set_period(period_reg);
+        pwm.counter.set_period_buff(period_reg);
+        pwm.counter.set_cc(cc);
+        pwm.counter.set_cc_buff(cc);
+        Ok(pwm)
+    }
+
+    /// tick_hz returns the counter clock rate after the prescaler.
+    #[inline(always)]
+    pub fn tick_hz(&self) -> u32 {
+        self.tick_hz
+    }
+    /// enable starts the PWM from the beginning of a period.
+    pub fn enable(&mut self) -> () {
+        self.counter.enable();
+        self.counter.reload();
+    }
+    /// disable stops the PWM, the outputs return to their inactive levels.
+    #[inline(always)]
+    pub fn disable(&mut self) -> () {
+        self.counter.disable();
+    }
+    /// period_ticks returns the period in counter ticks.
+    #[inline(always)]
+    pub fn period_ticks(&self) -> u32 {
+        self.period
+    }
+    /// set_period_ticks sets the period from the next terminal count,
+    /// the duty in ticks is kept, limited to the new period.
+    /// It returns an error when the period is out of range for the
+    /// counter.
+    pub fn set_period_ticks(&mut self, period_ticks: u32) -> Result<(), Error> {
+        let period_reg = period_register(self.alignment, period_ticks, C::MAX)?;
+        self.period = period_ticks;
+        self.duty = self.duty.min(self.max_duty_ticks());
+        self.switch(period_reg);
+        Ok(())
+    }
+    /// max_duty_ticks returns the duty of an always high line.
+    #[inline(always)]
+    pub fn max_duty_ticks(&self) -> u32 {
+        match self.alignment {
+            Alignment::Edge => self.period,
+            Alignment::Center => self.period & !1,
+        }
+    }
+    /// duty_ticks returns the duty in counter ticks.
+    #[inline(always)]
+    pub fn duty_ticks(&self) -> u32 {
+        self.duty
+    }
+    /// set_duty_ticks sets the duty, limited to max_duty_ticks, from the
+    /// next terminal count.
+    pub fn set_duty_ticks(&mut self, duty_ticks: u32) -> () {
+        self.duty = duty_ticks.min(self.max_duty_ticks());
+        // The period was checked when set.
+        let period_reg = period_register(self.alignment, self.period, C::MAX).unwrap_or(0);
+        self.switch(period_reg);
+    }
+    /// set_duty_fraction sets the duty to numerator / denominator of the
+    /// period, a denominator of 0 sets a duty of 0.
+    pub fn set_duty_fraction(&mut self, numerator: u32, denominator: u32) -> () {
+        let duty = match denominator {
+            0 => 0,
+            _ => self.max_duty_ticks() as u64 * numerator as u64 / denominator as u64,
+        };
+        self.set_duty_ticks(duty.min(u32::MAX as u64) as u32);
+    }
+    /// listen forwards an event to the counter interrupt.
+    #[inline(always)]
+    pub fn listen(&mut self, event: CounterEvent) -> () {
+        self.counter.listen(event);
+    }
+    /// unlisten stops forwarding an event to the counter interrupt.
+    #[inline(always)]
+    pub fn unlisten(&mut self, event: CounterEvent) -> () {
+        self.counter.unlisten(event);
+    }
+    /// clear_interrupt clears a pending event.
+    #[inline(always)]
+    pub fn clear_interrupt(&mut self, event: CounterEvent) -> () {
+        self.counter.clear_interrupt(event);
+    }
+    /// interrupt returns the interrupt source of the counter.
+    #[inline(always)]
+    pub fn interrupt(&self) -> InterruptSource {
+        C::INTERRUPT
+    }
+    /// free stops the PWM and returns the counter and the pins, still
+    /// connected to the counter outputs.
+    pub fn free(mut self) -> (C, L::Output, N::Output) {
+        self.counter.disable();
+        (self.counter, self.line, self.line_compl)
+    }
+
+    /// switch writes the buffer registers, which are exchanged with
+    /// PERIOD and CC on the next terminal count, both are written as an
+    /// exchange swaps both.
+    fn switch(&mut self, period_reg: u32) -> () {
+        self.counter.set_period_buff(period_reg);
+        self.counter.set_cc_buff(self.cc_for(self.duty));
+        self.counter.capture();
+    }
+    /// cc_for returns the compare value of a duty.
+    /// Edge aligned the line is set on the overflow and cleared on the
+    /// match, center aligned it is cleared at 0 and inverted on the
+    /// matches either side of PERIOD.
+    #[inline(always)]
+    fn cc_for(&self, duty: u32) -> u32 {
+        match self.alignment {
+            Alignment::Edge => duty,
+            Alignment::Center => (self.max_duty_ticks() - duty) / 2,
+        }
+    }
+}
+
+/// period_register returns the PERIOD value for a period in ticks, an
+/// up counter runs PERIOD + 1 ticks, an up/down counter 2 * PERIOD.
+/// The period is limited so the always high compare value fits.
+fn period_register(alignment: Alignment, period_ticks: u32, max: u32) -> Result<u32, Error> {
+    match alignment {
+        Alignment::Edge if (2..=max).contains(&period_ticks) => Ok(period_ticks - 1),
+        Alignment::Center if period_ticks >= 2 && period_ticks / 2 <= max => Ok(period_ticks / 2),
+        _ => Err(Error::PwmPeriodOutOfRange),
+    }
+}
+
+impl<C: TcpwmCounter, L: LinePin<C>, N: LineComplPin<C>> ehal::PwmPin for Pwm<C, L, N> {
+    type Duty = u32;
+
+    fn disable(&mut self) {
+        Pwm::disable(self);
+    }
+    fn enable(&mut self) {
+        Pwm::enable(self);
+    }
+    fn get_duty(&self) -> u32 {
+        self.duty_ticks()
+    }
+    fn get_max_duty(&self) -> u32 {
+        self.max_duty_ticks()
+    }
+    fn set_duty(&mut self, duty: u32) {
+        self.set_duty_ticks(duty);
+    }
+}
+
+/// Pwm has a single channel, line_n follows line.
+impl<C: TcpwmCounter, L: LinePin<C>, N: LineComplPin<C>> ehal::Pwm for Pwm<C, L, N> {
+    type Channel = ();
+    /// Time is in counter ticks.
+    type Time = u32;
+    type Duty = u32;
+
+    fn disable(&mut self, _channel: ()) {
+        Pwm::disable(self);
+    }
+    fn enable(&mut self, _channel: ()) {
+        Pwm::enable(self);
+    }
+    fn get_period(&self) -> u32 {
+        self.period_ticks()
+    }
+    fn get_duty(&self, _channel: ()) -> u32 {
+        self.duty_ticks()
+    }
+    fn get_max_duty(&self) -> u32 {
+        self.max_duty_ticks()
+    }
+    fn set_duty(&mut self, _channel: (), duty: u32) {
+        self.set_duty_ticks(duty);
+    }
+    /// set_period limits the period to the range of the counter.
+    fn set_period<P>(&mut self, period: P)
+    where
+        P: Into<u32>,
+    {
+        let period = period.into().max(2).min(match self.alignment {
+            Alignment::Edge => C::MAX,
+            Alignment::Center => C::MAX.saturating_mul(2),
+        });
+        // The period is within range.
+        let _ = self.set_period_ticks(period);
+    }
+}
+
+impl<C: TcpwmCounter, L: LinePin<C>, N: LineComplPin<C>> ehal1::pwm::ErrorType for Pwm<C, L, N> {
+    type Error = Infallible;
+}
+
+/// The u16 duty cycle is scaled to the duty in ticks, the 32-bit
+/// counters lose resolution above 65535 ticks.
+impl<C: TcpwmCounter, L: LinePin<C>, N: LineComplPin<C>> ehal1::pwm::SetDutyCycle for Pwm<C, L, N> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.max_duty_ticks().min(u16::MAX as u32) as u16
+    }
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        let max = self.max_duty_cycle() as u64;
+        let ticks = match max {
+            0 => 0,
+            _ => duty as u64 * self.max_duty_ticks() as u64 / max,
+        };
+        self.set_duty_ticks(ticks as u32);
+        Ok(())
+    }
+}
+
+macro_rules! line_pins {
+    ($hsiom:ident, [
+     $($CNT:ident: [$(($LINE:ident, $COMPL:ident)),+]),+
+    ]) => {
+        $(
+            $(
+                impl<MODE> LinePin<$CNT> for gpio::$LINE<MODE> {
+                    type Output = gpio::$LINE<Alternate<Strong>>;
+                    #[inline(always)]
+                    fn into_line(self, cs: &CriticalSection) -> Self::Output {
+                        self.into_alternate_strong_output(Hsiom::$hsiom, cs)
+                    }
+                }
+                impl<MODE> LineComplPin<$CNT> for gpio::$COMPL<MODE> {
+                    type Output = gpio::$COMPL<Alternate<Strong>>;
+                    #[inline(always)]
+                    fn into_line_compl(self, cs: &CriticalSection) -> Self::Output {
+                        self.into_alternate_strong_output(Hsiom::$hsiom, cs)
+                    }
+                }
+            )+
+        )+
+    };
+}
+
+// tcpwm[0].line[n] and tcpwm[0].line_compl[n]
+line_pins!(Act8, [
+    Tcpwm0Counter0: [(P0_0, P0_1), (P6_0, P6_1), (P8_0, P8_1), (P10_0, P10_1), (P12_0, P12_1)],
+    Tcpwm0Counter1: [(P0_2, P0_3), (P6_2, P6_3), (P8_2, P8_3), (P10_2, P10_3), (P12_2, P12_3)],
+    Tcpwm0Counter2: [(P0_4, P0_5), (P6_4, P6_5), (P8_4, P8_5), (P10_4, P10_5), (P12_4, P12_5)],
+    Tcpwm0Counter3: [(P1_0, P1_1), (P6_6, P6_7), (P8_6, P8_7), (P10_6, P10_7), (P12_6, P12_7)],
+    Tcpwm0Counter4: [(P1_2, P1_3), (P5_0, P5_1), (P7_0, P7_1), (P9_0, P9_1), (P11_0, P11_1), (P13_0, P13_1)],
+    Tcpwm0Counter5: [(P1_4, P1_5), (P5_2, P5_3), (P7_2, P7_3), (P9_2, P9_3), (P11_2, P11_3), (P13_2, P13_3)],
+    Tcpwm0Counter6: [(P5_4, P5_5), (P7_4, P7_5), (P9_4, P9_5), (P11_4, P11_5), (P13_4, P13_5)],
+    Tcpwm0Counter7: [(P5_6, P5_7), (P7_6, P7_7), (P9_6, P9_7), (P11_6, P11_7), (P13_6, P13_7)]
+]);
+
+// tcpwm[1].line[n] and tcpwm[1].line_compl[n]
+line_pins!(Act9, [
+    Tcpwm1Counter0: [(P0_0, P0_1), (P10_0, P10_1)],
+    Tcpwm1Counter1: [(P0_2, P0_3), (P10_2, P10_3)],
+    Tcpwm1Counter2: [(P0_4, P0_5), (P10_4, P10_5)],
+    Tcpwm1Counter3: [(P1_0, P1_1), (P10_6, P10_7)],
+    Tcpwm1Counter4: [(P5_0, P5_1), (P11_0, P11_1)],
+    Tcpwm1Counter5: [(P5_2, P5_3), (P11_2, P11_3)],
+    Tcpwm1Counter6: [(P5_4, P5_5), (P11_4, P11_5)],
+    Tcpwm1Counter7: [(P5_6, P5_7), (P11_6, P11_7)],
+    Tcpwm1Counter8: [(P6_0, P6_1), (P12_0, P12_1)],
+    Tcpwm1Counter9: [(P6_2, P6_3), (P12_2, P12_3)],
+    Tcpwm1Counter10: [(P6_4, P6_5), (P12_4, P12_5)],
+    Tcpwm1Counter11: [(P6_6, P6_7), (P12_6, P12_7)],
+    Tcpwm1Counter12: [(P1_2, P1_3), (P7_0, P7_1), (P13_0, P13_1)],
+    Tcpwm1Counter13: [(P1_4, P1_5), (P7_2, P7_3), (P13_2, P13_3)],
+    Tcpwm1Counter14: [(P7_4, P7_5), (P13_4, P13_5)],
+    Tcpwm1Counter15: [(P7_6, P7_7), (P13_6, P13_7)],
+    Tcpwm1Counter16: [(P8_0, P8_1)],
+    Tcpwm1Counter17: [(P8_2, P8_3)],
+    Tcpwm1Counter18: [(P8_4, P8_5)],
+    Tcpwm1Counter19: [(P8_6, P8_7)],
+    Tcpwm1Counter20: [(P9_0, P9_1)],
+    Tcpwm1Counter21: [(P9_2, P9_3)],
+    Tcpwm1Counter22: [(P9_4, P9_5)],
+    Tcpwm1Counter23: [(P9_6, P9_7)]
+]);
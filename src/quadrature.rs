@@ -0,0 +1,237 @@
+//! Quadrature decoder
+//!
+//! QuadratureDecoder runs a TCPWM counter in the quadrature mode, counting
+//! the phiA and phiB edges of a rotary or motor encoder at X1, X2 or X4
+//! resolution. The counter starts at the middle of its range, on a
+//! terminal count, at 0 or PERIOD, or on an index pulse the hardware
+//! copies the count to CC and restarts from the middle. update adds the
+//! copied count to a 64-bit software position, so the position never
+//! wraps as long as update is called, from the counter interrupt or a
+//! poll, within half the counter range of counts, 32768 counts on the
+//! 16-bit counters.
+//!```
+//! let mut decoder = QuadratureDecoder::new(counters.counter0, &dividers.div8_0, phi_a, phi_b, index, QuadratureMode::X4);
+//! decoder.listen(CounterEvent::TerminalCount);
+//! decoder.listen(CounterEvent::CompareMatch);
+//! decoder.enable();
+//! // in the counter interrupt
+//! decoder.update();
+//! let position = decoder.position();
+//!```
+
+use crate::drivers::cpuss::interrupt::InterruptSource;
+use crate::drivers::peri::PeriClockDivider;
+use crate::drivers::tcpwm::{
+    Control, CounterEvent, CounterMode, InputEdge, QuadratureMode, TcpwmCounter, TriggerInput,
+};
+use crate::gpio::AlternateInput;
+use crate::trigger::{TriggerPin, TriggerSource};
+
+/// Direction of the last count.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// QuadratureDecoder is a TCPWM counter in the quadrature mode owning
+/// the phiA and phiB pins and the index input, a TriggerPin or
+/// TriggerInput::Zero when the encoder has no index.
+pub struct QuadratureDecoder<C, A, B, I>
+where
+    C: TcpwmCounter,
+    A: AlternateInput,
+    B: AlternateInput,
+    I: TriggerSource,
+{
+    counter: C,
+    phi_a: TriggerPin<A>,
+    phi_b: TriggerPin<B>,
+    index: I,
+    reset_on_index: bool,
+    // position at the middle count.
+    base: i64,
+    index_count: u32,
+}
+
+impl<C, A, B, I> QuadratureDecoder<C, A, B, I>
+where
+    C: TcpwmCounter,
+    A: AlternateInput,
+    B: AlternateInput,
+    I: TriggerSource,
+{
+    /// MID is the count the counter starts from.
+    const MID: u32 = C::MAX / 2 + 1;
+
+    /// new connects the divider to the counter and configures a stopped
+    /// decoder at position 0. phiA is the count input, phiB the start
+    /// input and the index the reload input of the counter.
+    /// The divider must be set and enabled, at least four times faster
+    /// than the fastest phiA edges.
+    pub fn new<D: PeriClockDivider>(
+        mut counter: C,
+        divider: &D,
+        phi_a: TriggerPin<A>,
+        phi_b: TriggerPin<B>,
+        index: I,
+        resolution: QuadratureMode,
+    ) -> Self {
+        divider.connect(C::CLOCK);
+        counter.disable();
+        counter.configure(Control {
+            mode: CounterMode::Quadrature,
+            quadrature_mode: resolution,
+            ..Control::default()
+        });
+        counter.set_inputs(
+            TriggerInput::Zero,
+            phi_a.input(),
+            index.input(),
+            TriggerInput::Zero,
+            phi_b.input(),
+        );
+        // phiA and phiB are decoded as levels.
+        counter.set_input_edges(
+            InputEdge::Rising,
+            InputEdge::Level,
+            InputEdge::Rising,
+            InputEdge::Rising,
+            InputEdge::Level,
+        );
+        counter.set_period(C::MAX);
+        counter.set_counter(Self::MID);
+        counter.clear_interrupt(CounterEvent::TerminalCount);
+        counter.clear_interrupt(CounterEvent::CompareMatch);
+        QuadratureDecoder {
+            counter,
+            phi_a,
+            phi_b,
+            index,
+            reset_on_index: false,
+            base: 0,
+            index_count: 0,
+        }
+    }
+    /// enable starts decoding, the reload command starts the counter in
+    /// the quadrature mode.
+    pub fn enable(&mut self) -> () {
+        self.counter.enable();
+        self.counter.reload();
+    }
+    /// disable stops decoding, the position is kept.
+    pub fn disable(&mut self) -> () {
+        self.update();
+        self.base = self.position_now();
+        self.counter.disable();
+        self.counter.set_counter(Self::MID);
+    }
+    /// reset_on_index sets the position to 0 on each index pulse,
+    /// otherwise the index only counts.
+    #[inline(always)]
+    pub fn reset_on_index(&mut self, reset: bool) -> () {
+        self.reset_on_index = reset;
+    }
+    /// update adds the count copied on a terminal count or index to the
+    /// position. It returns true when an index pulse was seen.
+    /// Both events copy the count to CC, when both are pending CC holds
+    /// the index copy and the terminal count, 0 or PERIOD, is taken from
+    /// the direction. update must be called before the counter can
+    /// reach its range again.
+    pub fn update(&mut self) -> bool {
+        // The index (reload) event raises the compare match interrupt.
+        let index = self.counter.is_pending(CounterEvent::CompareMatch);
+        if self.counter.is_pending(CounterEvent::TerminalCount) {
+            self.counter.clear_interrupt(CounterEvent::TerminalCount);
+            self.base += match index {
+                true => self.terminal_count(),
+                false => self.copied_count(),
+            };
+        }
+        if index {
+            self.counter.clear_interrupt(CounterEvent::CompareMatch);
+            self.index_count = self.index_count.wrapping_add(1);
+            self.base = match self.reset_on_index {
+                true => 0,
+                false => self.base + self.copied_count(),
+            };
+        }
+        index
+    }
+    /// position returns the signed position in counts.
+    pub fn position(&mut self) -> i64 {
+        loop {
+            self.update();
+            let position = self.position_now();
+            // A terminal count or index between the update and the read
+            // restarted the counter, read again.
+            if !self.counter.is_pending(CounterEvent::TerminalCount)
+                && !self.counter.is_pending(CounterEvent::CompareMatch)
+            {
+                return position;
+            }
+        }
+    }
+    /// position_i32 returns the position wrapped to 32 bits.
+    #[inline(always)]
+    pub fn position_i32(&mut self) -> i32 {
+        self.position() as i32
+    }
+    /// set_position sets the current position.
+    pub fn set_position(&mut self, position: i64) -> () {
+        self.update();
+        self.base = position - (self.counter.counter() as i64 - Self::MID as i64);
+    }
+    /// direction returns the direction of the last count.
+    #[inline(always)]
+    pub fn direction(&self) -> Direction {
+        match self.counter.is_counting_down() {
+            true => Direction::Down,
+            false => Direction::Up,
+        }
+    }
+    /// index_count returns the number of index pulses seen by update.
+    #[inline(always)]
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+    /// listen forwards the terminal count or the index (CompareMatch)
+    /// to the counter interrupt.
+    #[inline(always)]
+    pub fn listen(&mut self, event: CounterEvent) -> () {
+        self.counter.listen(event);
+    }
+    #[inline(always)]
+    pub fn unlisten(&mut self, event: CounterEvent) -> () {
+        self.counter.unlisten(event);
+    }
+    /// interrupt returns the interrupt source of the counter.
+    #[inline(always)]
+    pub fn interrupt(&self) -> InterruptSource {
+        C::INTERRUPT
+    }
+    /// free stops the decoder and returns the counter and the inputs.
+    pub fn free(mut self) -> (C, TriggerPin<A>, TriggerPin<B>, I) {
+        self.counter.disable();
+        (self.counter, self.phi_a, self.phi_b, self.index)
+    }
+
+    #[inline(always)]
+    fn position_now(&self) -> i64 {
+        self.base + (self.counter.counter() as i64 - Self::MID as i64)
+    }
+    /// copied_count returns the count copied to CC relative to MID.
+    #[inline(always)]
+    fn copied_count(&self) -> i64 {
+        self.counter.cc() as i64 - Self::MID as i64
+    }
+    /// terminal_count returns the count of a terminal count relative to
+    /// MID, 0 counting down and PERIOD counting up.
+    #[inline(always)]
+    fn terminal_count(&self) -> i64 {
+        match self.counter.is_counting_down() {
+            true => -(Self::MID as i64),
+            false => C::MAX as i64 - Self::MID as i64,
+        }
+    }
+}
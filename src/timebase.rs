@@ -0,0 +1,124 @@
+//! Free-running timebase
+//!
+//! Timebase uses the SysTick of the core it runs on to count ticks
+//! since it was started, the SysTick exception adds the ticks of each
+//! wrap, about a millisecond, and the current SysTick count gives the
+//! ticks since the last wrap. The times are converted from the ticks at
+//! the exact tick rate. The SysTick is clocked either by the core clock,
+//! CLK_SLOW on the CM0+ and CLK_FAST on the CM4, or by the external clock
+//! selected with Cpuss::configure_systick_source.
+//! The SysTick exception must call Timebase::on_interrupt:
+//!```
+//! let timebase = Timebase::new_core(cp.SYST, &clocks);
+//!
+//! #[exception]
+//! fn SysTick() {
+//!     Timebase::on_interrupt();
+//! }
+//!
+//! let start = timebase.now_us();
+//!```
+
+use core::cell::Cell;
+
+use cortex_m::interrupt::{free, Mutex};
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::{SCB, SYST};
+
+use crate::clocks::Clocks;
+use crate::error::Error;
+
+// Ticks of the wraps counted by the SysTick exception.
+static TICKS: Mutex<Cell<u64>> = Mutex::new(Cell::new(0));
+
+/// Timebase is a free-running millisecond and microsecond counter.
+pub struct Timebase {
+    syst: SYST,
+    tick_hz: u32,
+}
+
+impl Timebase {
+    /// new_core starts the timebase on the core clock of the core the
+    /// code is built for.
+    pub fn new_core(syst: SYST, clocks: &Clocks) -> Result<Self, Error> {
+        Self::start(syst, SystClkSource::Core, clocks.core_clk())
+    }
+    /// new_external starts the timebase on the SysTick external clock
+    /// selected by Cpuss::configure_systick_source, source_hz is its
+    /// frequency, e.g. Clocks::lf_clk. The calibration is not used as
+    /// TENMS rounds the frequency down to 100Hz.
+    /// It returns an error when no external clock is available,
+    /// SYST_CALIB.NOREF.
+    pub fn new_external(syst: SYST, source_hz: u32) -> Result<Self, Error> {
+        if !SYST::has_reference_clock() {
+            return Err(Error::SystickReferenceNotAvailable);
+        }
+        Self::start(syst, SystClkSource::External, source_hz)
+    }
+    fn start(mut syst: SYST, source: SystClkSource, tick_hz: u32) -> Result<Self, Error> {
+        // The SysTick Reload Value register supports values between 1 and 0x00FFFFFF.
+        let reload = tick_hz / 1_000;
+        if reload < 2 || reload > 0x0100_0000 {
+            return Err(Error::SystickFrequencyOutOfRange);
+        }
+        syst.disable_counter();
+        syst.set_clock_source(source);
+        syst.set_reload(reload - 1);
+        syst.clear_current();
+        free(|cs| TICKS.borrow(cs).set(0));
+        syst.enable_interrupt();
+        syst.enable_counter();
+        Ok(Timebase { syst, tick_hz })
+    }
+    /// on_interrupt counts the ticks of a wrap, it must be called from
+    /// the SysTick exception handler.
+    #[inline(always)]
+    pub fn on_interrupt() -> () {
+        let reload = SYST::get_reload() as u64 + 1;
+        free(|cs| {
+            let ticks = TICKS.borrow(cs);
+            ticks.set(ticks.get() + reload);
+        });
+    }
+    /// tick_hz returns the SysTick clock rate.
+    #[inline(always)]
+    pub fn tick_hz(&self) -> u32 {
+        self.tick_hz
+    }
+    /// now_ticks returns the SysTick ticks since the timebase started.
+    pub fn now_ticks(&self) -> u64 {
+        let reload = SYST::get_reload() as u64 + 1;
+        free(|cs| {
+            let ticks = TICKS.borrow(cs).get();
+            let current = SYST::get_current() as u64;
+            // A wrap not yet counted by the exception, interrupts are
+            // masked so the SysTick exception is still pending.
+            match SCB::is_pendst_pending() {
+                true => ticks + reload + reload - 1 - SYST::get_current() as u64,
+                false => ticks + reload - 1 - current,
+            }
+        })
+    }
+    /// now_ms returns the milliseconds since the timebase started.
+    pub fn now_ms(&self) -> u64 {
+        self.ticks_to(self.now_ticks(), 1_000)
+    }
+    /// now_us returns the microseconds since the timebase started.
+    pub fn now_us(&self) -> u64 {
+        self.ticks_to(self.now_ticks(), 1_000_000)
+    }
+
+    /// ticks_to converts ticks to units of 1/per_second seconds, split
+    /// in whole seconds and the remainder so it cannot overflow.
+    #[inline(always)]
+    fn ticks_to(&self, ticks: u64, per_second: u64) -> u64 {
+        let tick_hz = self.tick_hz as u64;
+        ticks / tick_hz * per_second + ticks % tick_hz * per_second / tick_hz
+    }
+    /// free stops the timebase and releases the SysTick.
+    pub fn free(mut self) -> SYST {
+        self.syst.disable_interrupt();
+        self.syst.disable_counter();
+        self.syst
+    }
+}
@@ -0,0 +1,236 @@
+//! Timers
+//!
+//! Timer runs a TCPWM counter in the timer mode, counting up from 0 to
+//! a period set by start. The counter is clocked by a PERI clock
+//! divider, which may be shared with other peripherals, divided again
+//! by the counter prescaler. A Continuous timer restarts on each
+//! terminal count and is Periodic, a OneShot timer stops, see
+//! Timer::into_one_shot.
+//! The terminal count (overflow) and compare match events can be
+//! forwarded to the counter interrupt, see Timer::interrupt for the
+//! InterruptSource to map on the CM0+.
+//!```
+//! let counters = p.TCPWM1.split();
+//! let mut dividers = p.PERI.split();
+//! dividers.div16_0.set_frequency(&clocks, 1_000_000)?;
+//! dividers.div16_0.enable();
+//! let mut timer = Timer::new(counters.counter0, &dividers.div16_0, Prescaler::Div1);
+//! timer.start(timer.us_to_ticks(1_000));
+//! loop {
+//!     nb::block!(timer.wait()).unwrap();
+//! }
+//!```
+
+use core::marker::PhantomData;
+
+use crate::delay::DelayTimer;
+use crate::drivers::cpuss::interrupt::InterruptSource;
+use crate::drivers::peri::PeriClockDivider;
+use crate::drivers::tcpwm::{
+    Control, CounterEvent, CounterMode, InputEdge, Prescaler, TcpwmCounter, TriggerInput,
+};
+use crate::ehal::timer::{Cancel, CountDown, Periodic};
+use crate::error::Error;
+use void::Void;
+
+/// TimerMode selects what the counter does on a terminal count, it is
+/// part of the Timer type so only a Continuous timer is Periodic.
+pub trait TimerMode {
+    /// ONE_SHOT stops the counter on the terminal count.
+    const ONE_SHOT: bool;
+}
+
+/// Continuous timers restart on each terminal count.
+pub struct Continuous;
+/// OneShot timers stop on the terminal count.
+pub struct OneShot;
+
+impl TimerMode for Continuous {
+    const ONE_SHOT: bool = false;
+}
+impl TimerMode for OneShot {
+    const ONE_SHOT: bool = true;
+}
+
+/// Timer is a TCPWM counter in the timer mode.
+pub struct Timer<C: TcpwmCounter, M: TimerMode = Continuous> {
+    counter: C,
+    prescaler: Prescaler,
+    tick_hz: u32,
+    _mode: PhantomData<M>,
+}
+
+impl<C: TcpwmCounter> Timer<C, Continuous> {
+    /// new connects the divider to the counter and configures a
+    /// continuous timer counting at the divider frequency divided by the
+    /// prescaler. The divider must be set and enabled.
+    pub fn new<D: PeriClockDivider>(mut counter: C, divider: &D, prescaler: Prescaler) -> Self {
+        divider.connect(C::CLOCK);
+        counter.disable();
+        // Count every counter clock, no trigger inputs.
+        counter.set_inputs(
+            TriggerInput::Zero,
+            TriggerInput::One,
+            TriggerInput::Zero,
+            TriggerInput::Zero,
+            TriggerInput::Zero,
+        );
+        counter.set_input_edges(
+            InputEdge::Rising,
+            InputEdge::Level,
+            InputEdge::Rising,
+            InputEdge::Rising,
+            InputEdge::Rising,
+        );
+        Timer {
+            counter,
+            prescaler,
+            tick_hz: divider.frequency() >> (prescaler as u32),
+            _mode: PhantomData,
+        }
+    }
+    /// into_one_shot stops the timer and returns it as a one-shot timer.
+    pub fn into_one_shot(self) -> Timer<C, OneShot> {
+        self.into_mode()
+    }
+}
+
+impl<C: TcpwmCounter> Timer<C, OneShot> {
+    /// into_continuous stops the timer and returns it as a continuous
+    /// timer.
+    pub fn into_continuous(self) -> Timer<C, Continuous> {
+        self.into_mode()
+    }
+}
+
+impl<C: TcpwmCounter, M: TimerMode> Timer<C, M> {
+    /// tick_hz returns the counter clock rate after the prescaler.
+    #[inline(always)]
+    pub fn tick_hz(&self) -> u32 {
+        self.tick_hz
+    }
+    /// us_to_ticks returns the number of ticks in us microseconds,
+    /// saturating at the longest period of the counter.
+    pub fn us_to_ticks(&self, us: u32) -> u32 {
+        let ticks = us as u64 * self.tick_hz as u64 / 1_000_000;
+        ticks.min(self.max_ticks() as u64) as u32
+    }
+    /// value returns the current count, 0..ticks.
+    #[inline(always)]
+    pub fn value(&self) -> u32 {
+        self.counter.counter()
+    }
+    /// set_compare sets the count raising the compare match event.
+    #[inline(always)]
+    pub fn set_compare(&mut self, count: u32) -> () {
+        self.counter.set_cc(count);
+    }
+    /// listen forwards an event to the counter interrupt.
+    #[inline(always)]
+    pub fn listen(&mut self, event: CounterEvent) -> () {
+        self.counter.listen(event);
+    }
+    /// unlisten stops forwarding an event to the counter interrupt.
+    #[inline(always)]
+    pub fn unlisten(&mut self, event: CounterEvent) -> () {
+        self.counter.unlisten(event);
+    }
+    #[inline(always)]
+    pub fn is_pending(&self, event: CounterEvent) -> bool {
+        self.counter.is_pending(event)
+    }
+    /// clear_interrupt clears a pending event, the interrupt handler
+    /// must clear the events it handles.
+    #[inline(always)]
+    pub fn clear_interrupt(&mut self, event: CounterEvent) -> () {
+        self.counter.clear_interrupt(event);
+    }
+    /// interrupt returns the interrupt source of the counter.
+    #[inline(always)]
+    pub fn interrupt(&self) -> InterruptSource {
+        C::INTERRUPT
+    }
+    /// free stops the timer and returns the counter.
+    pub fn free(mut self) -> C {
+        self.counter.disable();
+        self.counter
+    }
+
+    fn into_mode<N: TimerMode>(mut self) -> Timer<C, N> {
+        self.counter.disable();
+        Timer {
+            counter: self.counter,
+            prescaler: self.prescaler,
+            tick_hz: self.tick_hz,
+            _mode: PhantomData,
+        }
+    }
+    /// restart runs the counter for ticks, 1..=max_ticks, from 0.
+    fn restart(&mut self, ticks: u32, one_shot: bool) -> () {
+        // Disabling resets the counter and any pending command.
+        self.counter.disable();
+        self.counter.configure(Control {
+            mode: CounterMode::Timer,
+            one_shot,
+            generic: self.prescaler as u8,
+            ..Control::default()
+        });
+        self.counter.set_counter(0);
+        self.counter.set_period(ticks.max(1).min(self.max_ticks()) - 1);
+        self.counter.clear_interrupt(CounterEvent::TerminalCount);
+        self.counter.enable();
+        self.counter.start();
+    }
+}
+
+impl<C: TcpwmCounter, M: TimerMode> CountDown for Timer<C, M> {
+    /// Time is in counter ticks, see Timer::us_to_ticks.
+    type Time = u32;
+
+    fn start<T>(&mut self, count: T)
+    where
+        T: Into<u32>,
+    {
+        self.restart(count.into(), M::ONE_SHOT);
+    }
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        match self.counter.is_pending(CounterEvent::TerminalCount) {
+            true => {
+                self.counter.clear_interrupt(CounterEvent::TerminalCount);
+                Ok(())
+            }
+            false => Err(nb::Error::WouldBlock),
+        }
+    }
+}
+
+impl<C: TcpwmCounter> Periodic for Timer<C, Continuous> {}
+
+impl<C: TcpwmCounter, M: TimerMode> Cancel for Timer<C, M> {
+    type Error = Error;
+
+    fn cancel(&mut self) -> Result<(), Error> {
+        if !self.counter.is_running() {
+            return Err(Error::TimerNotRunning);
+        }
+        self.counter.stop();
+        Ok(())
+    }
+}
+
+impl<C: TcpwmCounter, M: TimerMode> DelayTimer for Timer<C, M> {
+    #[inline(always)]
+    fn tick_hz(&self) -> u32 {
+        self.tick_hz
+    }
+    #[inline(always)]
+    fn max_ticks(&self) -> u32 {
+        // PERIOD + 1 ticks, 2^32 - 1 on the 32 bit counters.
+        C::MAX.saturating_add(1)
+    }
+    fn wait_ticks(&mut self, ticks: u32) {
+        self.restart(ticks, true);
+        while !self.counter.is_pending(CounterEvent::TerminalCount) {}
+        self.counter.clear_interrupt(CounterEvent::TerminalCount);
+    }
+}
@@ -0,0 +1,64 @@
+//! Trigger pins
+//!
+//! The TCPWM counter inputs, e.g. the quadrature phiA and phiB or the
+//! capture input, are trigger inputs. A pin reaches a counter through
+//! its peri.tr_io_input connection in the HSIOM and a route of the
+//! trigger multiplexer to one of the tr_in lines of the TCPWM block.
+//!```
+//! // The peri.tr_io_input connection of the pin, see the pinout tables.
+//! let phi_a = cortex_m::interrupt::free(|cs| gpio.p10_0.into_alternate_input(TR_IO_HSIOM, cs));
+//! // The route from the peri.tr_io_input line to tcpwm[0].tr_in[2],
+//! // see the trigger tables.
+//! let phi_a = TriggerPin::new(phi_a, TriggerRoute::new(TCPWM0_TRIGGER_GROUP, TR_IO_INPUT, 2));
+//!```
+
+use crate::drivers::peri::trigger::{connect_trigger, disconnect_trigger, TriggerRoute};
+use crate::drivers::tcpwm::TriggerInput;
+use crate::gpio::AlternateInput;
+
+/// TriggerSource is a counter input.
+pub trait TriggerSource {
+    /// input returns the TR_CTRL0 selection of the counter input.
+    fn input(&self) -> TriggerInput;
+}
+
+/// A trigger already routed, or a constant input, e.g. TriggerInput::Zero
+/// for an unused input.
+impl TriggerSource for TriggerInput {
+    #[inline(always)]
+    fn input(&self) -> TriggerInput {
+        *self
+    }
+}
+
+/// TriggerPin is a pin routed to a tr_in line of a TCPWM block.
+pub struct TriggerPin<P: AlternateInput> {
+    pin: P,
+    route: TriggerRoute,
+}
+
+impl<P: AlternateInput> TriggerPin<P> {
+    /// new connects the route, its output is the tr_in line of the TCPWM
+    /// block the pin is used on.
+    pub fn new(pin: P, route: TriggerRoute) -> Self {
+        connect_trigger(route);
+        TriggerPin { pin, route }
+    }
+    /// is_high reads the pin level.
+    #[inline(always)]
+    pub fn is_high(&self) -> bool {
+        self.pin.is_high()
+    }
+    /// free disconnects the route and returns the pin.
+    pub fn free(self) -> P {
+        disconnect_trigger(self.route);
+        self.pin
+    }
+}
+
+impl<P: AlternateInput> TriggerSource for TriggerPin<P> {
+    #[inline(always)]
+    fn input(&self) -> TriggerInput {
+        TriggerInput::Trigger(self.route.output)
+    }
+}